@@ -36,6 +36,9 @@ async fn buy_asset(client: &GateFiRestClient) {
         redirect_url: "google.com".to_string(),
         region: "BR".to_string(),
         wallet_address: "2Mxsqy9d6LuW2VYQPsojmPWXaRznMQ7Nifr".to_string(),
+        wallet_memo: None,
+        exchange_wallet: false,
+        partner_fee: None,
     };
     let result = client.buy_asset(params).await;
     println!("buy_asset result: {result:?}");
@@ -0,0 +1,40 @@
+use unlimit_connector::rest::models::GateFiTransactionStatus;
+use unlimit_connector::webhook::simulator::GateFiWebhookSimulator;
+
+// cargo run --example webhook_replay -- <url> <secret> <init|created|succeeded|failed|file.json>
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [url, secret, source] = args.as_slice() else {
+        eprintln!("usage: webhook_replay <url> <secret> <status|file.json>");
+        std::process::exit(2);
+    };
+    let simulator = GateFiWebhookSimulator::new(secret.to_owned());
+
+    let result = if std::path::Path::new(source).exists() {
+        simulator.replay_file(url, source).await
+    } else {
+        match source.parse::<GateFiTransactionStatus>() {
+            Ok(status) => {
+                let callback = GateFiWebhookSimulator::sample_callback(status);
+                simulator.deliver(url, &callback).await.map(|d| vec![d])
+            }
+            Err(err) => {
+                eprintln!("{source} is neither a file nor a status: {err}");
+                std::process::exit(2);
+            }
+        }
+    };
+
+    match result {
+        Ok(deliveries) => {
+            for delivery in deliveries {
+                println!("{} {}", delivery.status, delivery.response_body);
+            }
+        }
+        Err(err) => {
+            eprintln!("delivery failed: {err}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use error_chain::bail;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use unlimit_connector::payment_page::{PaymentPage, PaymentPageConfig};
+use unlimit_connector::rest::config::{GateFiConfig, GateFiEnvironment};
+use unlimit_connector::rest::errors::Error;
+use unlimit_connector::rest::request_signer::GateFiSigner;
+use unlimit_connector::rest::rest_client::{GateFiBuyAssetParams, GateFiRestClient};
+
+#[derive(Parser)]
+#[command(name = "gatefi", about = "Inspect the GateFi API")]
+struct Cli {
+    /// TOML or JSON file with `GateFiConfig` fields, overridden by `GATEFI_*` env vars
+    #[arg(long, env = "GATEFI_CONFIG")]
+    config: Option<PathBuf>,
+    /// Overrides the configured environment
+    #[arg(long, value_enum)]
+    env: Option<Environment>,
+    #[arg(long, value_enum, default_value_t = Output::Json)]
+    output: Output,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Environment {
+    Sandbox,
+    Prod,
+    Custom,
+}
+
+impl From<Environment> for GateFiEnvironment {
+    fn from(env: Environment) -> Self {
+        match env {
+            Environment::Sandbox => GateFiEnvironment::Sandbox,
+            Environment::Prod => GateFiEnvironment::Prod,
+            Environment::Custom => GateFiEnvironment::Custom,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Output {
+    Json,
+    Table,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    Config,
+    PaymentConfig,
+    Methods {
+        #[arg(long)]
+        currency: String,
+        #[arg(long)]
+        country: String,
+    },
+    Rates,
+    Quote(QuoteArgs),
+    BuyUrl {
+        #[command(flatten)]
+        quote: QuoteArgs,
+        #[arg(long)]
+        wallet: String,
+        /// Destination tag or memo, e.g. for XRP and BNB.
+        #[arg(long)]
+        memo: Option<String>,
+        /// The wallet is an exchange deposit address, so a memo is required on XRP and BNB.
+        #[arg(long)]
+        exchange_wallet: bool,
+        #[arg(long, default_value = "")]
+        redirect_url: String,
+        #[arg(long)]
+        order_id: Option<String>,
+    },
+    PaymentPageUrl {
+        #[arg(long)]
+        wallet: String,
+        /// Destination tag or memo, e.g. for XRP and BNB.
+        #[arg(long)]
+        memo: Option<String>,
+        /// The wallet is an exchange deposit address, so a memo is required on XRP and BNB.
+        #[arg(long)]
+        exchange_wallet: bool,
+        #[arg(long)]
+        fiat: String,
+        #[arg(long)]
+        amount: String,
+        #[arg(long)]
+        crypto: String,
+        #[arg(long, default_value = "")]
+        external_id: String,
+        #[arg(long)]
+        lock: bool,
+    },
+    VerifyWebhook {
+        /// Defaults to the configured webhook secret
+        #[arg(long)]
+        secret: Option<String>,
+        /// The signature covers the exact bytes of this file, including any trailing newline
+        #[arg(long)]
+        body_file: PathBuf,
+        #[arg(long)]
+        signature: String,
+        /// Strip trailing line breaks, e.g. added by an editor, before verifying
+        #[arg(long)]
+        trim: bool,
+    },
+}
+
+#[derive(Args)]
+struct QuoteArgs {
+    #[arg(long)]
+    amount: String,
+    #[arg(long)]
+    crypto: String,
+    #[arg(long)]
+    fiat: String,
+    #[arg(long, default_value = "BANKCARD")]
+    payment: String,
+    #[arg(long)]
+    region: String,
+}
+
+fn load_config(cli: &Cli) -> Result<GateFiConfig, Error> {
+    let mut config = match &cli.config {
+        Some(path) => GateFiConfig::from_file(path)?,
+        None => GateFiConfig::default(),
+    };
+    config.apply_env()?;
+    if let Some(env) = cli.env {
+        config.environment = env.into();
+    }
+    config.validate()?;
+
+    Ok(config)
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    match run(&cli).await {
+        Ok(value) => print(&value, cli.output),
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run(cli: &Cli) -> Result<Value, Error> {
+    if let Command::VerifyWebhook {
+        secret: Some(secret),
+        body_file,
+        signature,
+        trim,
+    } = &cli.command
+    {
+        return verify_webhook(secret, body_file, signature, *trim);
+    }
+
+    let gatefi_config = load_config(cli)?;
+    let client = GateFiRestClient::from_config(&gatefi_config)?;
+
+    let value = match &cli.command {
+        Command::Config => serde_json::to_value(client.get_platform_config().await?)?,
+        Command::PaymentConfig => serde_json::to_value(client.get_payment_config().await?)?,
+        Command::Methods { currency, country } => {
+            serde_json::to_value(client.get_payment_methods(currency, country).await?)?
+        }
+        Command::Rates => serde_json::to_value(client.get_rates().await?)?,
+        Command::Quote(args) => serde_json::to_value(
+            client
+                .get_quote(
+                    &args.amount,
+                    &args.crypto,
+                    &args.fiat,
+                    &args.payment,
+                    &args.region,
+                )
+                .await?,
+        )?,
+        Command::BuyUrl {
+            quote,
+            wallet,
+            memo,
+            exchange_wallet,
+            redirect_url,
+            order_id,
+        } => {
+            let params = GateFiBuyAssetParams {
+                amount: quote.amount.clone(),
+                crypto: quote.crypto.clone(),
+                fiat: quote.fiat.clone(),
+                order_custom_id: order_id.clone().unwrap_or_default(),
+                payment_method: quote.payment.clone(),
+                redirect_url: redirect_url.clone(),
+                region: quote.region.clone(),
+                wallet_address: wallet.clone(),
+                wallet_memo: memo.clone(),
+                exchange_wallet: *exchange_wallet,
+                partner_fee: None,
+            };
+            serde_json::to_value(client.buy_asset(params).await?)?
+        }
+        Command::PaymentPageUrl {
+            wallet,
+            memo,
+            exchange_wallet,
+            fiat,
+            amount,
+            crypto,
+            external_id,
+            lock,
+        } => {
+            let mut config = PaymentPageConfig {
+                wallet: wallet.clone(),
+                wallet_lock: *lock,
+                wallet_memo: None,
+                fiat_currency: fiat.clone(),
+                fiat_currency_lock: *lock,
+                fiat_amount: amount.clone(),
+                fiat_amount_lock: *lock,
+                crypto_currency: crypto.clone(),
+                external_id: external_id.clone(),
+                partner_account_id: gatefi_config.partner_id.clone(),
+                partner_fee_params: Default::default(),
+                exchange_wallet: *exchange_wallet,
+            };
+            if let Some(memo) = memo {
+                config = config.with_wallet_memo(memo)?;
+            }
+            config.validate()?;
+            let page = PaymentPage::from_config(&gatefi_config, config);
+            serde_json::json!({ "url": page.into_url() })
+        }
+        Command::VerifyWebhook {
+            body_file,
+            signature,
+            trim,
+            ..
+        } => verify_webhook(gatefi_config.webhook_secret(), body_file, signature, *trim)?,
+    };
+
+    Ok(value)
+}
+
+fn verify_webhook(
+    secret: &str,
+    body_file: &Path,
+    signature: &str,
+    trim: bool,
+) -> Result<Value, Error> {
+    let bytes = std::fs::read(body_file)?;
+    let Ok(mut body) = std::str::from_utf8(&bytes) else {
+        bail!("Webhook body in {} is not valid utf-8", body_file.display());
+    };
+    if trim {
+        body = body.trim_end_matches(['\r', '\n']);
+    }
+
+    Ok(serde_json::json!({
+        "valid": GateFiSigner::verify_sign(secret, body, signature),
+    }))
+}
+
+fn print(value: &Value, output: Output) {
+    match output {
+        Output::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(value).expect("must be valid json")
+        ),
+        Output::Table => {
+            let mut rows = Vec::new();
+            flatten("", value, &mut rows);
+            let width = rows.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+            for (key, value) in rows {
+                println!("{key:width$}  {value}");
+            }
+        }
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, rows: &mut Vec<(String, String)>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{prefix}.{key}")
+        }
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(&join(key), value, rows);
+            }
+        }
+        Value::Array(items) => {
+            for (index, value) in items.iter().enumerate() {
+                flatten(&join(&index.to_string()), value, rows);
+            }
+        }
+        Value::String(value) => rows.push((prefix.to_string(), value.clone())),
+        value => rows.push((prefix.to_string(), value.to_string())),
+    }
+}
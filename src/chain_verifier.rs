@@ -0,0 +1,342 @@
+use crate::rest::currency::Currency;
+use crate::rest::errors::Error;
+use crate::rest::explorer::Network;
+use crate::rest::models::{GateFiCallbackData, GateFiTransactionStatus};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ChainLookup {
+    /// Blockchain as in `CURRENCIES`, e.g. `BTC` or `ERC20`.
+    pub blockchain: String,
+    /// Currency id as in `CURRENCIES`, to tell tokens apart on the same chain.
+    pub currency: String,
+    pub network: Network,
+    pub tx_hash: String,
+    pub expected_address: String,
+    pub expected_amount: Decimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ChainTransfer {
+    pub tx_hash: String,
+    pub confirmations: u64,
+    /// Sum of all outputs of the currency to `expected_address`; zero when none.
+    pub matched_amount: Decimal,
+    /// Every recipient of the currency in the transaction.
+    pub recipients: Vec<String>,
+}
+
+/// Looks up a transaction on chain.
+///
+/// Implementations return `Ok(None)` when the transaction is unknown to the chain and `Err`
+/// only when the lookup itself failed, so a node outage is not reported as a missing delivery.
+#[async_trait]
+pub trait ChainVerifier: Send + Sync {
+    async fn lookup(&self, lookup: &ChainLookup) -> Result<Option<ChainTransfer>, Error>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum DeliveryIssue {
+    NotSucceeded {
+        status: String,
+    },
+    UnknownChain {
+        crypto: String,
+    },
+    InvalidAmount {
+        amount: String,
+    },
+    NoTransactionHashes,
+    TransactionNotFound {
+        tx_hash: String,
+    },
+    WrongRecipient {
+        tx_hash: String,
+        recipients: Vec<String>,
+    },
+    AmountMismatch {
+        expected: Decimal,
+        received: Decimal,
+    },
+    InsufficientConfirmations {
+        tx_hash: String,
+        confirmations: u64,
+        required: u64,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Verified,
+    /// Everything matches but confirmations are still below the required count.
+    Pending,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeliveryVerification {
+    pub transaction_id: String,
+    pub status: DeliveryStatus,
+    pub issues: Vec<DeliveryIssue>,
+    pub matched_amount: Decimal,
+    pub transfers: Vec<ChainTransfer>,
+}
+
+/// Confirms that a succeeded callback's crypto actually reached `destination_wallet`.
+pub struct DeliveryVerifier {
+    chain: Arc<dyn ChainVerifier>,
+    network: Network,
+    min_confirmations: u64,
+    amount_tolerance: Decimal,
+}
+
+impl DeliveryVerifier {
+    pub fn new(chain: Arc<dyn ChainVerifier>, network: Network) -> Self {
+        Self {
+            chain,
+            network,
+            min_confirmations: 1,
+            amount_tolerance: Decimal::ZERO,
+        }
+    }
+
+    pub fn with_min_confirmations(mut self, min_confirmations: u64) -> Self {
+        self.min_confirmations = min_confirmations;
+        self
+    }
+
+    /// Allowed absolute difference between the callback and on-chain amounts.
+    pub fn with_amount_tolerance(mut self, amount_tolerance: Decimal) -> Self {
+        self.amount_tolerance = amount_tolerance;
+        self
+    }
+
+    pub async fn verify(
+        &self,
+        callback: &GateFiCallbackData,
+    ) -> Result<DeliveryVerification, Error> {
+        let mut verification = DeliveryVerification {
+            transaction_id: callback.transaction_id.clone(),
+            status: DeliveryStatus::Failed,
+            issues: Vec::new(),
+            matched_amount: Decimal::ZERO,
+            transfers: Vec::new(),
+        };
+
+        if callback.parsed_status() != GateFiTransactionStatus::Succeeded {
+            verification.issues.push(DeliveryIssue::NotSucceeded {
+                status: callback.status.clone(),
+            });
+            return Ok(verification);
+        }
+        let Some(currency) = Currency::find(&callback.crypto_currency) else {
+            verification.issues.push(DeliveryIssue::UnknownChain {
+                crypto: callback.crypto_currency.clone(),
+            });
+            return Ok(verification);
+        };
+        let Ok(expected_amount) = callback.parsed_crypto_amount() else {
+            verification.issues.push(DeliveryIssue::InvalidAmount {
+                amount: callback.crypto_amount.clone(),
+            });
+            return Ok(verification);
+        };
+        let hashes = callback.transaction_hashes.clone().unwrap_or_default();
+        if hashes.is_empty() {
+            verification.issues.push(DeliveryIssue::NoTransactionHashes);
+            return Ok(verification);
+        }
+
+        let mut pending = false;
+        for tx_hash in hashes {
+            let lookup = ChainLookup {
+                blockchain: currency.blockchain.to_string(),
+                currency: currency.id.to_string(),
+                network: self.network,
+                tx_hash: tx_hash.clone(),
+                expected_address: callback.destination_wallet.clone(),
+                expected_amount,
+            };
+            let Some(transfer) = self.chain.lookup(&lookup).await? else {
+                verification
+                    .issues
+                    .push(DeliveryIssue::TransactionNotFound { tx_hash });
+                continue;
+            };
+
+            if transfer.matched_amount.is_zero() {
+                verification.issues.push(DeliveryIssue::WrongRecipient {
+                    tx_hash: tx_hash.clone(),
+                    recipients: transfer.recipients.clone(),
+                });
+            }
+            if transfer.confirmations < self.min_confirmations {
+                pending = true;
+            }
+            verification.matched_amount += transfer.matched_amount;
+            verification.transfers.push(transfer);
+        }
+
+        let difference = (verification.matched_amount - expected_amount).abs();
+        if verification.issues.is_empty() && difference > self.amount_tolerance {
+            verification.issues.push(DeliveryIssue::AmountMismatch {
+                expected: expected_amount,
+                received: verification.matched_amount,
+            });
+        }
+
+        verification.status = match (verification.issues.is_empty(), pending) {
+            (false, _) => DeliveryStatus::Failed,
+            (true, true) => DeliveryStatus::Pending,
+            (true, false) => DeliveryStatus::Verified,
+        };
+        if verification.status == DeliveryStatus::Pending {
+            for transfer in &verification.transfers {
+                if transfer.confirmations < self.min_confirmations {
+                    verification
+                        .issues
+                        .push(DeliveryIssue::InsufficientConfirmations {
+                            tx_hash: transfer.tx_hash.clone(),
+                            confirmations: transfer.confirmations,
+                            required: self.min_confirmations,
+                        });
+                }
+            }
+        }
+
+        Ok(verification)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MockTransaction {
+    outputs: Vec<(String, Decimal)>,
+    confirmations: u64,
+}
+
+/// In-memory chain for tests and sandbox runs.
+#[derive(Debug, Default)]
+pub struct MockChainVerifier {
+    transactions: Mutex<HashMap<String, MockTransaction>>,
+}
+
+impl MockChainVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_transaction(
+        &self,
+        tx_hash: impl Into<String>,
+        outputs: Vec<(String, Decimal)>,
+        confirmations: u64,
+    ) {
+        self.transactions.lock().unwrap().insert(
+            tx_hash.into(),
+            MockTransaction {
+                outputs,
+                confirmations,
+            },
+        );
+    }
+
+    pub fn set_confirmations(&self, tx_hash: &str, confirmations: u64) {
+        if let Some(transaction) = self.transactions.lock().unwrap().get_mut(tx_hash) {
+            transaction.confirmations = confirmations;
+        }
+    }
+}
+
+#[async_trait]
+impl ChainVerifier for MockChainVerifier {
+    async fn lookup(&self, lookup: &ChainLookup) -> Result<Option<ChainTransfer>, Error> {
+        let transactions = self.transactions.lock().unwrap();
+        let Some(transaction) = transactions.get(&lookup.tx_hash) else {
+            return Ok(None);
+        };
+
+        Ok(Some(ChainTransfer {
+            tx_hash: lookup.tx_hash.clone(),
+            confirmations: transaction.confirmations,
+            matched_amount: transaction
+                .outputs
+                .iter()
+                .filter(|(address, _)| same_address(address, &lookup.expected_address))
+                .map(|(_, amount)| *amount)
+                .sum(),
+            recipients: transaction
+                .outputs
+                .iter()
+                .map(|(address, _)| address.clone())
+                .collect(),
+        }))
+    }
+}
+
+/// EVM addresses are compared case-insensitively, as their case is only a checksum.
+pub fn same_address(a: &str, b: &str) -> bool {
+    if a.starts_with("0x") && b.starts_with("0x") {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhook::simulator::GateFiWebhookSimulator;
+
+    fn callback(hash: &str, amount: &str) -> GateFiCallbackData {
+        let mut callback =
+            GateFiWebhookSimulator::sample_callback(GateFiTransactionStatus::Succeeded);
+        callback.crypto_currency = "BTC".to_string();
+        callback.crypto_amount = amount.to_string();
+        callback.destination_wallet = "wallet".to_string();
+        callback.transaction_hashes = Some(vec![hash.to_string()]);
+
+        callback
+    }
+
+    #[tokio::test]
+    async fn verify_delivery() {
+        let chain = Arc::new(MockChainVerifier::new());
+        chain.add_transaction("tx1", vec![("wallet".to_string(), Decimal::new(1, 3))], 0);
+        chain.add_transaction("tx2", vec![("other".to_string(), Decimal::new(1, 3))], 6);
+        let verifier =
+            DeliveryVerifier::new(chain.clone(), Network::Testnet).with_min_confirmations(1);
+
+        let pending = verifier.verify(&callback("tx1", "0.001")).await.unwrap();
+        assert_eq!(DeliveryStatus::Pending, pending.status);
+
+        chain.set_confirmations("tx1", 3);
+        let verified = verifier.verify(&callback("tx1", "0.001")).await.unwrap();
+        assert_eq!(DeliveryStatus::Verified, verified.status);
+
+        let mismatch = verifier.verify(&callback("tx1", "0.002")).await.unwrap();
+        assert!(matches!(
+            mismatch.issues[0],
+            DeliveryIssue::AmountMismatch { .. }
+        ));
+
+        let wrong = verifier.verify(&callback("tx2", "0.001")).await.unwrap();
+        assert!(matches!(
+            wrong.issues[0],
+            DeliveryIssue::WrongRecipient { .. }
+        ));
+
+        let missing = verifier.verify(&callback("tx3", "0.001")).await.unwrap();
+        assert_eq!(
+            vec![DeliveryIssue::TransactionNotFound {
+                tx_hash: "tx3".to_string()
+            }],
+            missing.issues
+        );
+    }
+}
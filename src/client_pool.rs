@@ -0,0 +1,181 @@
+use crate::rest::config::GateFiConfig;
+use crate::rest::errors::{Error, ErrorKind};
+use crate::rest::models::GateFiCallbackData;
+use crate::rest::rest_client::GateFiRestClient;
+use crate::rest::transport::{GateFiTransport, ReqwestTransport};
+use crate::webhook::GateFiWebhookVerifier;
+use error_chain::bail;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+#[derive(Clone)]
+struct GateFiPartner {
+    config: GateFiConfig,
+    client: GateFiRestClient,
+    verifier: GateFiWebhookVerifier,
+}
+
+/// Clients for several partners keyed by brand, all sharing one transport and connection pool.
+pub struct GateFiClientPool {
+    transport: Arc<dyn GateFiTransport>,
+    partners: RwLock<HashMap<String, GateFiPartner>>,
+}
+
+impl GateFiClientPool {
+    pub fn new() -> Self {
+        Self::with_transport(Arc::new(ReqwestTransport::new()))
+    }
+
+    pub fn with_transport(transport: Arc<dyn GateFiTransport>) -> Self {
+        Self {
+            transport,
+            partners: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces the partner when `key` is already registered.
+    pub fn add_partner(&self, key: impl Into<String>, config: GateFiConfig) -> Result<(), Error> {
+        let client = GateFiRestClient::from_config_with_transport(&config, self.transport.clone())?;
+        let partner = GateFiPartner {
+            verifier: GateFiWebhookVerifier::from_config(&config),
+            client,
+            config,
+        };
+        self.partners.write().unwrap().insert(key.into(), partner);
+
+        Ok(())
+    }
+
+    pub fn remove_partner(&self, key: &str) -> bool {
+        self.partners.write().unwrap().remove(key).is_some()
+    }
+
+    pub fn partners(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.partners.read().unwrap().keys().cloned().collect();
+        keys.sort();
+
+        keys
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.partners.read().unwrap().contains_key(key)
+    }
+
+    pub fn client(&self, key: &str) -> Result<GateFiRestClient, Error> {
+        Ok(self.partner(key)?.client)
+    }
+
+    pub fn config(&self, key: &str) -> Result<GateFiConfig, Error> {
+        Ok(self.partner(key)?.config)
+    }
+
+    /// Finds the partner by its GateFi `partner_id`.
+    pub fn key_by_partner_id(&self, partner_id: &str) -> Option<String> {
+        self.partners
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, partner)| partner.config.partner_id == partner_id)
+            .map(|(key, _)| key.clone())
+    }
+
+    pub fn verify_webhook(
+        &self,
+        key: &str,
+        body: &str,
+        signature: &str,
+    ) -> Result<GateFiCallbackData, Error> {
+        self.partner(key)?.verifier.verify(body, signature)
+    }
+
+    /// Tries every partner's webhook secret, for endpoints shared by all brands. Fails when
+    /// the signature matches more than one partner, e.g. brands sharing a secret.
+    pub fn identify_webhook(
+        &self,
+        body: &str,
+        signature: &str,
+    ) -> Result<(String, GateFiCallbackData), Error> {
+        let mut partners: Vec<(String, GateFiWebhookVerifier)> = self
+            .partners
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, partner)| (key.clone(), partner.verifier.clone()))
+            .collect();
+        partners.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut matched = Vec::new();
+        for (key, verifier) in partners {
+            match verifier.verify(body, signature) {
+                Err(Error(ErrorKind::InvalidSignature, _)) => continue,
+                result => matched.push((key, result?)),
+            }
+        }
+
+        match matched.len() {
+            0 => Err(ErrorKind::InvalidSignature.into()),
+            1 => Ok(matched.remove(0)),
+            _ => {
+                let keys: Vec<String> = matched.into_iter().map(|(key, _)| key).collect();
+                bail!(
+                    "Webhook signature matches several partners: {}",
+                    keys.join(", ")
+                )
+            }
+        }
+    }
+
+    fn partner(&self, key: &str) -> Result<GateFiPartner, Error> {
+        let Some(partner) = self.partners.read().unwrap().get(key).cloned() else {
+            bail!("Unknown GateFi partner {}", key);
+        };
+
+        Ok(partner)
+    }
+}
+
+impl Default for GateFiClientPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::models::GateFiTransactionStatus;
+    use crate::webhook::simulator::GateFiWebhookSimulator;
+
+    fn config(partner_id: &str) -> GateFiConfig {
+        GateFiConfig::sandbox(
+            partner_id.to_string(),
+            format!("{partner_id}-access"),
+            format!("{partner_id}-secret"),
+        )
+    }
+
+    #[test]
+    fn route_webhooks_by_secret() {
+        let pool = GateFiClientPool::new();
+        pool.add_partner("brand-a", config("a")).unwrap();
+        pool.add_partner("brand-b", config("b")).unwrap();
+
+        let callback = GateFiWebhookSimulator::sample_callback(GateFiTransactionStatus::Succeeded);
+        let body = serde_json::to_string(&callback).unwrap();
+        let signature = GateFiWebhookSimulator::new("b-secret".to_string()).sign(&body);
+
+        let (key, _) = pool.identify_webhook(&body, &signature).unwrap();
+        assert_eq!("brand-b", key);
+        assert!(pool.verify_webhook("brand-a", &body, &signature).is_err());
+        assert_eq!(Some("brand-a".to_string()), pool.key_by_partner_id("a"));
+
+        pool.add_partner("brand-c", config("b")).unwrap();
+        let err = pool.identify_webhook(&body, &signature).unwrap_err();
+        assert!(err.to_string().ends_with("brand-b, brand-c"));
+        assert!(pool.remove_partner("brand-c"));
+
+        assert!(pool.remove_partner("brand-b"));
+        assert!(pool.identify_webhook(&body, &signature).is_err());
+        assert!(pool.client("brand-b").is_err());
+    }
+}
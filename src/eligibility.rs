@@ -0,0 +1,484 @@
+use crate::rest::errors::Error;
+use crate::rest::models::{GateFiCurrencyPaymentMethod, GateFiPaymentConfigResponse};
+use crate::rest::rest_client::GateFiRestClient;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Payment method requests `check` sends at once.
+const PAYMENT_METHOD_FETCHES: usize = 4;
+
+/// Payment methods by fiat and country.
+type MethodsCache = HashMap<(String, String), Vec<GateFiCurrencyPaymentMethod>>;
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum IneligibilityReason {
+    NationalityNotSupported {
+        nationality: String,
+    },
+    ResidenceNotSupported {
+        country: String,
+    },
+    FiatNotSupported {
+        fiat: String,
+    },
+    CryptoNotSupported {
+        crypto: String,
+    },
+    /// Reported for every checked fiat when none of them has a method in the country.
+    NoPaymentMethods {
+        fiat: String,
+        country: String,
+    },
+    /// The config has no list to check against, e.g. `availableCountries`.
+    CountryListMissing {
+        list: String,
+    },
+}
+
+impl fmt::Display for IneligibilityReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IneligibilityReason::NationalityNotSupported { nationality } => {
+                write!(f, "Nationality {nationality} is not supported")
+            }
+            IneligibilityReason::ResidenceNotSupported { country } => {
+                write!(f, "Residence country {country} is not supported")
+            }
+            IneligibilityReason::FiatNotSupported { fiat } => {
+                write!(f, "Fiat currency {fiat} is not supported")
+            }
+            IneligibilityReason::CryptoNotSupported { crypto } => {
+                write!(f, "Crypto currency {crypto} is not supported")
+            }
+            IneligibilityReason::NoPaymentMethods { fiat, country } => {
+                write!(f, "No payment methods available for {fiat} in {country}")
+            }
+            IneligibilityReason::CountryListMissing { list } => {
+                write!(f, "Config has no {list} to check against")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct EligibilityRequest {
+    /// ISO country code, as in `available_nationalities`.
+    pub nationality: String,
+    /// ISO country code, as in `available_countries`.
+    pub residence_country: String,
+    pub fiat: Option<String>,
+    pub crypto: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AllowedPaymentMethod {
+    pub fiat: String,
+    pub method: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct CryptoLimits {
+    pub crypto: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EligibilityResult {
+    pub eligible: bool,
+    /// Empty when eligible.
+    pub reasons: Vec<IneligibilityReason>,
+    pub fiat_currencies: Vec<String>,
+    pub payment_methods: Vec<AllowedPaymentMethod>,
+    pub crypto_limits: Option<CryptoLimits>,
+}
+
+/// Checks a customer against the payment config before sending them to the widget.
+#[derive(Debug, Clone)]
+pub struct EligibilityChecker {
+    config: GateFiPaymentConfigResponse,
+    /// Kept as long as the config.
+    methods_cache: Arc<Mutex<MethodsCache>>,
+}
+
+impl EligibilityChecker {
+    pub fn new(config: GateFiPaymentConfigResponse) -> Self {
+        Self {
+            config,
+            methods_cache: Default::default(),
+        }
+    }
+
+    pub async fn load(client: &GateFiRestClient) -> Result<Self, Error> {
+        Ok(Self::new(client.get_payment_config().await?))
+    }
+
+    pub fn config(&self) -> &GateFiPaymentConfigResponse {
+        &self.config
+    }
+
+    /// Payment methods differ per country, so they are fetched for the residence country and
+    /// cached per fiat and country.
+    pub async fn check(
+        &self,
+        client: &GateFiRestClient,
+        request: &EligibilityRequest,
+    ) -> Result<EligibilityResult, Error> {
+        let country = request.residence_country.trim().to_uppercase();
+        let mut country_methods = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let cache = self.methods_cache.lock().unwrap();
+            for fiat in self.fiat_currencies(request).unwrap_or_default() {
+                match cache.get(&(fiat.clone(), country.clone())) {
+                    Some(methods) => {
+                        country_methods.insert(fiat, methods.clone());
+                    }
+                    None => missing.push(fiat),
+                }
+            }
+        }
+
+        let fetched: Vec<Result<(String, Vec<GateFiCurrencyPaymentMethod>), Error>> =
+            stream::iter(missing)
+                .map(|fiat| {
+                    let country = country.clone();
+                    async move {
+                        let methods = client.get_payment_methods(fiat.clone(), country).await?;
+                        Ok((fiat, methods.list.unwrap_or_default()))
+                    }
+                })
+                .buffer_unordered(PAYMENT_METHOD_FETCHES)
+                .collect()
+                .await;
+        for result in fetched {
+            let (fiat, methods) = result?;
+            self.methods_cache
+                .lock()
+                .unwrap()
+                .insert((fiat.clone(), country.clone()), methods.clone());
+            country_methods.insert(fiat, methods);
+        }
+
+        Ok(self.check_with_methods(request, &country_methods))
+    }
+
+    /// Like `check`, with the methods `get_payment_methods` returned per fiat for the residence
+    /// country. A missing country list fails the check, as it may just have been left out.
+    pub fn check_with_methods(
+        &self,
+        request: &EligibilityRequest,
+        country_methods: &HashMap<String, Vec<GateFiCurrencyPaymentMethod>>,
+    ) -> EligibilityResult {
+        let mut reasons = Vec::new();
+
+        let nationality = request.nationality.trim().to_uppercase();
+        match is_listed(&self.config.available_nationalities, &nationality) {
+            Some(true) => {}
+            Some(false) => {
+                reasons.push(IneligibilityReason::NationalityNotSupported { nationality })
+            }
+            None => reasons.push(IneligibilityReason::CountryListMissing {
+                list: "availableNationalities".to_string(),
+            }),
+        }
+        let country = request.residence_country.trim().to_uppercase();
+        match is_listed(&self.config.available_countries, &country) {
+            Some(true) => {}
+            Some(false) => reasons.push(IneligibilityReason::ResidenceNotSupported {
+                country: country.clone(),
+            }),
+            None => reasons.push(IneligibilityReason::CountryListMissing {
+                list: "availableCountries".to_string(),
+            }),
+        }
+
+        let fiat_currencies = match (self.fiat_currencies(request), &request.fiat) {
+            (Some(fiats), _) => fiats,
+            (None, fiat) => {
+                reasons.push(IneligibilityReason::FiatNotSupported {
+                    fiat: fiat.clone().unwrap_or_default().to_uppercase(),
+                });
+                Vec::new()
+            }
+        };
+
+        let mut payment_methods: Vec<AllowedPaymentMethod> = fiat_currencies
+            .iter()
+            .flat_map(|fiat| {
+                let available = country_methods.get(fiat).map(Vec::as_slice).unwrap_or(&[]);
+                self.config.fiat_assets[fiat]
+                    .methods
+                    .iter()
+                    .filter(move |(method, _)| {
+                        available
+                            .iter()
+                            .any(|available| available.code.eq_ignore_ascii_case(method))
+                    })
+                    .map(|(method, info)| AllowedPaymentMethod {
+                        fiat: fiat.clone(),
+                        method: method.clone(),
+                        min: info.min,
+                        max: info.max,
+                    })
+            })
+            .collect();
+        payment_methods.sort_by(|a, b| (&a.fiat, &a.method).cmp(&(&b.fiat, &b.method)));
+        if payment_methods.is_empty() {
+            reasons.extend(fiat_currencies.iter().map(|fiat| {
+                IneligibilityReason::NoPaymentMethods {
+                    fiat: fiat.clone(),
+                    country: country.clone(),
+                }
+            }));
+        }
+
+        let crypto_limits = match &request.crypto {
+            Some(crypto) => {
+                let asset = self
+                    .config
+                    .crypto_assets
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(crypto));
+                match asset {
+                    Some((key, asset)) => Some(CryptoLimits {
+                        crypto: key.clone(),
+                        min: asset.min,
+                        max: asset.max,
+                    }),
+                    None => {
+                        reasons.push(IneligibilityReason::CryptoNotSupported {
+                            crypto: crypto.to_uppercase(),
+                        });
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
+        EligibilityResult {
+            eligible: reasons.is_empty(),
+            reasons,
+            fiat_currencies,
+            payment_methods,
+            crypto_limits,
+        }
+    }
+
+    /// `None` when the requested fiat is not in the config.
+    fn fiat_currencies(&self, request: &EligibilityRequest) -> Option<Vec<String>> {
+        match &request.fiat {
+            Some(fiat) => self.fiat_key(fiat).map(|key| vec![key]),
+            None => {
+                let mut fiats: Vec<String> = self.config.fiat_assets.keys().cloned().collect();
+                fiats.sort();
+                Some(fiats)
+            }
+        }
+    }
+
+    fn fiat_key(&self, fiat: &str) -> Option<String> {
+        self.config
+            .fiat_assets
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(fiat))
+            .cloned()
+    }
+}
+
+/// `None` when the list is empty, as then nothing is known about the country.
+fn is_listed(list: &[String], country: &str) -> Option<bool> {
+    if list.is_empty() {
+        return None;
+    }
+
+    Some(list.iter().any(|item| item.eq_ignore_ascii_case(country)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::config::GateFiApiConfig;
+    use crate::rest::endpoints::RawResponse;
+    use crate::rest::transport::{GateFiTransport, PreparedRequest};
+    use async_trait::async_trait;
+    use reqwest::header::HeaderMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn checker() -> EligibilityChecker {
+        let json = r#"{
+            "availableNationalities": ["DE", "FR"],
+            "availableCountries": ["DE"],
+            "fiat": {"EUR": {"methods": {
+                "BANKCARD": {"min": 20.0, "max": 5000.0},
+                "SEPA": {"min": 50.0, "max": 10000.0}
+            }}},
+            "crypto": {"BTC": {"type": "BTC", "symbol": "BTC", "min": 0.001, "max": 1.0}}
+        }"#;
+
+        EligibilityChecker::new(serde_json::from_str(json).unwrap())
+    }
+
+    fn methods(fiat: &str, codes: &[&str]) -> HashMap<String, Vec<GateFiCurrencyPaymentMethod>> {
+        let list = codes
+            .iter()
+            .map(|code| serde_json::from_str(&format!(r#"{{"code":"{code}"}}"#)).unwrap())
+            .collect();
+
+        HashMap::from([(fiat.to_string(), list)])
+    }
+
+    #[test]
+    fn check_eligibility() {
+        let checker = checker();
+
+        let result = checker.check_with_methods(
+            &EligibilityRequest {
+                nationality: "fr".to_string(),
+                residence_country: "DE".to_string(),
+                fiat: Some("eur".to_string()),
+                crypto: Some("BTC".to_string()),
+            },
+            &methods("EUR", &["bankcard"]),
+        );
+        assert!(result.eligible);
+        assert_eq!(vec!["EUR".to_string()], result.fiat_currencies);
+        // SEPA is configured for EUR but not offered in the residence country
+        assert_eq!(1, result.payment_methods.len());
+        assert_eq!(5000.0, result.payment_methods[0].max);
+        assert_eq!(0.001, result.crypto_limits.unwrap().min);
+
+        let result = checker.check_with_methods(
+            &EligibilityRequest {
+                nationality: "US".to_string(),
+                residence_country: "FR".to_string(),
+                fiat: Some("USD".to_string()),
+                crypto: None,
+            },
+            &HashMap::new(),
+        );
+        assert!(!result.eligible);
+        assert_eq!(
+            vec![
+                IneligibilityReason::NationalityNotSupported {
+                    nationality: "US".to_string()
+                },
+                IneligibilityReason::ResidenceNotSupported {
+                    country: "FR".to_string()
+                },
+                IneligibilityReason::FiatNotSupported {
+                    fiat: "USD".to_string()
+                },
+            ],
+            result.reasons
+        );
+    }
+
+    #[test]
+    fn missing_lists_fail_closed() {
+        let mut checker = checker();
+        checker.config.available_countries.clear();
+        let request = EligibilityRequest {
+            nationality: "DE".to_string(),
+            residence_country: "DE".to_string(),
+            fiat: Some("EUR".to_string()),
+            crypto: None,
+        };
+
+        let result = checker.check_with_methods(&request, &methods("EUR", &["SEPA"]));
+        assert!(!result.eligible);
+        assert_eq!(
+            vec![IneligibilityReason::CountryListMissing {
+                list: "availableCountries".to_string()
+            }],
+            result.reasons
+        );
+
+        checker.config.available_countries = vec!["DE".to_string()];
+        let result = checker.check_with_methods(&request, &HashMap::new());
+        assert_eq!(
+            vec![IneligibilityReason::NoPaymentMethods {
+                fiat: "EUR".to_string(),
+                country: "DE".to_string()
+            }],
+            result.reasons
+        );
+    }
+
+    /// Offers no payment methods anywhere.
+    #[derive(Default)]
+    struct EmptyMethodsTransport {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl GateFiTransport for EmptyMethodsTransport {
+        async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+
+            Ok(RawResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: HeaderMap::new(),
+                body: r#"{"list":[]}"#.to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_methods_once_per_fiat_and_country() {
+        let transport = Arc::new(EmptyMethodsTransport::default());
+        let client = GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(transport.clone());
+        let checker = EligibilityChecker::new(
+            serde_json::from_str(
+                r#"{
+                    "availableNationalities": ["DE"],
+                    "availableCountries": ["DE"],
+                    "fiat": {
+                        "EUR": {"methods": {"SEPA": {"min": 50.0, "max": 10000.0}}},
+                        "USD": {"methods": {"BANKCARD": {"min": 20.0, "max": 5000.0}}}
+                    }
+                }"#,
+            )
+            .unwrap(),
+        );
+        let request = EligibilityRequest {
+            nationality: "DE".to_string(),
+            residence_country: "de".to_string(),
+            fiat: None,
+            crypto: None,
+        };
+
+        let result = checker.check(&client, &request).await.unwrap();
+        assert_eq!(
+            vec![
+                IneligibilityReason::NoPaymentMethods {
+                    fiat: "EUR".to_string(),
+                    country: "DE".to_string()
+                },
+                IneligibilityReason::NoPaymentMethods {
+                    fiat: "USD".to_string(),
+                    country: "DE".to_string()
+                },
+            ],
+            result.reasons
+        );
+        assert_eq!(2, transport.calls.load(Ordering::SeqCst));
+
+        checker.clone().check(&client, &request).await.unwrap();
+        assert_eq!(2, transport.calls.load(Ordering::SeqCst));
+    }
+}
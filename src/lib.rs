@@ -1,2 +1,13 @@
 pub mod rest;
 pub mod payment_page;
+pub mod rate_book;
+pub mod order_store;
+pub mod order_tracker;
+pub mod webhook;
+pub mod client_pool;
+pub mod eligibility;
+pub mod limits;
+pub mod reporting;
+pub mod chain_verifier;
+pub mod payment_uri;
+pub mod slippage;
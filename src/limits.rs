@@ -0,0 +1,364 @@
+use crate::rate_book::RateBook;
+use crate::rest::errors::Error;
+use crate::rest::models::{
+    GateFiPaymentConfigResponse, GateFiPlatformAsset, GateFiPlatformConfigResponse,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+const DEFAULT_HINT_PRECISION: usize = 8;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitSource {
+    PlatformConfig,
+    PaymentConfig,
+    /// Both configs define the limit; the overlap of the ranges is used.
+    Both,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AssetLimit {
+    pub asset: String,
+    pub min: f64,
+    pub max: f64,
+    /// Decimal places used by `hint`.
+    pub precision: Option<usize>,
+    pub source: LimitSource,
+}
+
+impl AssetLimit {
+    pub fn contains(&self, amount: f64) -> bool {
+        amount >= self.min && amount <= self.max
+    }
+
+    pub fn with_precision(mut self, precision: usize) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Renders e.g. `between 20 and 5000 EUR`.
+    pub fn hint(&self) -> String {
+        let precision = self.precision.unwrap_or(DEFAULT_HINT_PRECISION);
+
+        format!(
+            "between {} and {} {}",
+            format_amount(self.min, precision),
+            format_amount(self.max, precision),
+            self.asset
+        )
+    }
+
+    /// The precision is dropped, as it belongs to the original asset.
+    pub fn convert(&self, rates: &RateBook, to: &str) -> Result<AssetLimit, Error> {
+        Ok(AssetLimit {
+            asset: to.to_uppercase(),
+            min: rates.convert(self.min, &self.asset, to)?.converted_amount,
+            max: rates.convert(self.max, &self.asset, to)?.converted_amount,
+            precision: None,
+            source: self.source,
+        })
+    }
+}
+
+/// Platform and payment config limits whose ranges do not overlap.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct LimitConflict {
+    pub asset: String,
+    /// `None` for crypto limits.
+    pub method: Option<String>,
+    pub platform: AssetLimit,
+    pub payment: AssetLimit,
+}
+
+/// The limits of each config, kept so a reload of one config replaces its own limit.
+#[derive(Debug, Clone, Default)]
+struct LimitEntry {
+    platform: Option<AssetLimit>,
+    payment: Option<AssetLimit>,
+    /// `None` when the two ranges do not overlap.
+    resolved: Option<AssetLimit>,
+}
+
+impl LimitEntry {
+    fn set(&mut self, limit: AssetLimit) {
+        match limit.source {
+            LimitSource::PlatformConfig => self.platform = Some(limit),
+            LimitSource::PaymentConfig | LimitSource::Both => self.payment = Some(limit),
+        }
+
+        self.resolved = match (&self.platform, &self.payment) {
+            (Some(platform), Some(payment)) => {
+                let limit = AssetLimit {
+                    asset: payment.asset.clone(),
+                    min: platform.min.max(payment.min),
+                    max: platform.max.min(payment.max),
+                    precision: payment.precision.or(platform.precision),
+                    source: LimitSource::Both,
+                };
+                (limit.min <= limit.max).then_some(limit)
+            }
+            (Some(limit), None) | (None, Some(limit)) => Some(limit.clone()),
+            (None, None) => None,
+        };
+    }
+
+    fn conflict(&self, method: Option<&str>) -> Option<LimitConflict> {
+        match (&self.platform, &self.payment, &self.resolved) {
+            (Some(platform), Some(payment), None) => Some(LimitConflict {
+                asset: payment.asset.clone(),
+                method: method.map(str::to_string),
+                platform: platform.clone(),
+                payment: payment.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Fiat limits per payment method and crypto limits from the platform and payment configs.
+#[derive(Debug, Clone, Default)]
+pub struct LimitsIndex {
+    fiat: HashMap<(String, String), LimitEntry>,
+    crypto: HashMap<String, LimitEntry>,
+}
+
+impl LimitsIndex {
+    pub fn new(
+        platform_config: Option<&GateFiPlatformConfigResponse>,
+        payment_config: Option<&GateFiPaymentConfigResponse>,
+    ) -> Self {
+        let mut index = Self::default();
+        if let Some(config) = platform_config {
+            index.add_platform_config(config);
+        }
+        if let Some(config) = payment_config {
+            index.add_payment_config(config);
+        }
+
+        index
+    }
+
+    pub fn add_platform_config(&mut self, config: &GateFiPlatformConfigResponse) {
+        for asset in &config.fiat_assets {
+            for (method, limit) in platform_limits(asset) {
+                let key = (asset.id.to_uppercase(), method.to_uppercase());
+                insert(&mut self.fiat, key, limit);
+            }
+        }
+
+        // Crypto limits are given per method; the widest range is the overall limit.
+        for asset in &config.crypto_assets {
+            let overall = platform_limits(asset)
+                .into_iter()
+                .map(|(_, limit)| limit)
+                .reduce(|acc, limit| AssetLimit {
+                    min: acc.min.min(limit.min),
+                    max: acc.max.max(limit.max),
+                    ..acc
+                });
+            if let Some(limit) = overall {
+                insert(&mut self.crypto, asset.id.to_uppercase(), limit);
+            }
+        }
+    }
+
+    pub fn add_payment_config(&mut self, config: &GateFiPaymentConfigResponse) {
+        for (fiat, asset) in &config.fiat_assets {
+            for (method, info) in &asset.methods {
+                let limit = AssetLimit {
+                    asset: fiat.to_uppercase(),
+                    min: info.min,
+                    max: info.max,
+                    precision: info.precision.and_then(|p| usize::try_from(p).ok()),
+                    source: LimitSource::PaymentConfig,
+                };
+                insert(
+                    &mut self.fiat,
+                    (fiat.to_uppercase(), method.to_uppercase()),
+                    limit,
+                );
+            }
+        }
+
+        for (crypto, asset) in &config.crypto_assets {
+            let limit = AssetLimit {
+                asset: crypto.to_uppercase(),
+                min: asset.min,
+                max: asset.max,
+                precision: asset.precision.and_then(|p| usize::try_from(p).ok()),
+                source: LimitSource::PaymentConfig,
+            };
+            insert(&mut self.crypto, crypto.to_uppercase(), limit);
+        }
+    }
+
+    /// `None` also when the configs conflict, see `conflicts`.
+    pub fn fiat_limit(&self, fiat: &str, method: &str) -> Option<&AssetLimit> {
+        self.fiat
+            .get(&(fiat.to_uppercase(), method.to_uppercase()))
+            .and_then(|entry| entry.resolved.as_ref())
+    }
+
+    /// `None` also when the configs conflict, see `conflicts`.
+    pub fn crypto_limit(&self, crypto: &str) -> Option<&AssetLimit> {
+        self.crypto
+            .get(&crypto.to_uppercase())
+            .and_then(|entry| entry.resolved.as_ref())
+    }
+
+    pub fn conflicts(&self) -> Vec<LimitConflict> {
+        let mut conflicts: Vec<LimitConflict> = self
+            .fiat
+            .iter()
+            .filter_map(|((_, method), entry)| entry.conflict(Some(method)))
+            .chain(
+                self.crypto
+                    .values()
+                    .filter_map(|entry| entry.conflict(None)),
+            )
+            .collect();
+        conflicts.sort_by(|a, b| (&a.asset, &a.method).cmp(&(&b.asset, &b.method)));
+
+        conflicts
+    }
+
+    pub fn methods(&self, fiat: &str) -> Vec<String> {
+        let fiat = fiat.to_uppercase();
+        let mut methods: Vec<String> = self
+            .fiat
+            .keys()
+            .filter(|(key, _)| *key == fiat)
+            .map(|(_, method)| method.clone())
+            .collect();
+        methods.sort();
+
+        methods
+    }
+
+    pub fn fiat_limit_in(
+        &self,
+        fiat: &str,
+        method: &str,
+        to: &str,
+        rates: &RateBook,
+    ) -> Result<Option<AssetLimit>, Error> {
+        self.fiat_limit(fiat, method)
+            .map(|limit| limit.convert(rates, to))
+            .transpose()
+    }
+
+    pub fn crypto_limit_in(
+        &self,
+        crypto: &str,
+        to: &str,
+        rates: &RateBook,
+    ) -> Result<Option<AssetLimit>, Error> {
+        self.crypto_limit(crypto)
+            .map(|limit| limit.convert(rates, to))
+            .transpose()
+    }
+}
+
+fn insert<K: std::hash::Hash + Eq>(map: &mut HashMap<K, LimitEntry>, key: K, limit: AssetLimit) {
+    map.entry(key).or_default().set(limit);
+}
+
+/// Limits with unparsable amounts are skipped.
+fn platform_limits(asset: &GateFiPlatformAsset) -> Vec<(String, AssetLimit)> {
+    asset
+        .payment_limits
+        .iter()
+        .flatten()
+        .filter_map(|limit| {
+            let min = limit.min.trim().parse().ok()?;
+            let max = limit.max.trim().parse().ok()?;
+            Some((
+                limit.id.clone(),
+                AssetLimit {
+                    asset: asset.id.to_uppercase(),
+                    min,
+                    max,
+                    precision: None,
+                    source: LimitSource::PlatformConfig,
+                },
+            ))
+        })
+        .collect()
+}
+
+fn format_amount(amount: f64, precision: usize) -> String {
+    let formatted = format!("{amount:.precision$}");
+    if !formatted.contains('.') {
+        return formatted;
+    }
+
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconcile_and_convert() {
+        let platform: GateFiPlatformConfigResponse = serde_json::from_str(
+            r#"{"fiat": [{"id": "EUR", "paymentLimits": [{"id": "BANKCARD", "min": "10", "max": "5000"}]}]}"#,
+        )
+        .unwrap();
+        let payment: GateFiPaymentConfigResponse = serde_json::from_str(
+            r#"{"fiat": {"EUR": {"methods": {"BANKCARD": {"min": 20.0, "max": 10000.0, "precision": 2}}}}}"#,
+        )
+        .unwrap();
+        let rates = RateBook::new(
+            serde_json::from_str(r#"{"list":{"EUR":{"rates":{"USD":1.25}}}}"#).unwrap(),
+        );
+
+        let index = LimitsIndex::new(Some(&platform), Some(&payment));
+        let limit = index.fiat_limit("eur", "bankcard").unwrap();
+        assert_eq!(LimitSource::Both, limit.source);
+        assert_eq!("between 20 and 5000 EUR", limit.hint());
+
+        let usd = index
+            .fiat_limit_in("EUR", "BANKCARD", "USD", &rates)
+            .unwrap()
+            .unwrap()
+            .with_precision(2);
+        assert_eq!("between 25 and 6250 USD", usd.hint());
+        assert!(index.fiat_limit("EUR", "SEPA").is_none());
+    }
+
+    #[test]
+    fn reload_and_conflicts() {
+        let platform = |min: &str, max: &str| -> GateFiPlatformConfigResponse {
+            serde_json::from_str(&format!(
+                r#"{{"fiat": [{{"id": "EUR", "paymentLimits": [{{"id": "BANKCARD", "min": "{min}", "max": "{max}"}}]}}]}}"#
+            ))
+            .unwrap()
+        };
+        let payment: GateFiPaymentConfigResponse = serde_json::from_str(
+            r#"{"fiat": {"EUR": {"methods": {"BANKCARD": {"min": 20.0, "max": 10000.0}}}}}"#,
+        )
+        .unwrap();
+
+        let mut index = LimitsIndex::new(Some(&platform("10", "5000")), Some(&payment));
+        // a reloaded platform config widens the range again
+        index.add_platform_config(&platform("10", "8000"));
+        assert_eq!(
+            "between 20 and 8000 EUR",
+            index.fiat_limit("EUR", "BANKCARD").unwrap().hint()
+        );
+        assert!(index.conflicts().is_empty());
+
+        index.add_platform_config(&platform("1", "10"));
+        assert!(index.fiat_limit("EUR", "BANKCARD").is_none());
+        let conflicts = index.conflicts();
+        assert_eq!(1, conflicts.len());
+        assert_eq!(Some("BANKCARD".to_string()), conflicts[0].method);
+        assert_eq!(10.0, conflicts[0].platform.max);
+        assert_eq!(20.0, conflicts[0].payment.min);
+    }
+}
@@ -0,0 +1,462 @@
+use crate::rest::errors::Error;
+use crate::rest::models::GateFiBuyAssetResponse;
+use crate::rest::rest_client::{GateFiBuyAssetParams, GateFiRestClient};
+use async_trait::async_trait;
+use error_chain::bail;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+#[derive(Debug)]
+pub struct OrderIdGenerator {
+    prefix: String,
+    counter: AtomicU64,
+}
+
+impl OrderIdGenerator {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    pub fn generate(&self) -> String {
+        let micros = unix_micros().unwrap_or_default();
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+
+        format!(
+            "{}-{}-{}-{}",
+            self.prefix,
+            micros,
+            std::process::id(),
+            counter
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct StoredOrder {
+    pub idempotency_key: String,
+    pub params: GateFiBuyAssetParams,
+    pub redirect_url: Option<String>,
+    /// Http status of the completed buy request; 0 for orders completed before it was kept.
+    #[serde(default)]
+    pub status: u16,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl StoredOrder {
+    pub fn is_completed(&self) -> bool {
+        self.redirect_url.is_some()
+    }
+}
+
+#[async_trait]
+pub trait OrderStore: Send + Sync {
+    async fn get(&self, idempotency_key: &str) -> Result<Option<StoredOrder>, Error>;
+    /// Returns `false` without overwriting when the key is already stored.
+    async fn insert(&self, order: StoredOrder) -> Result<bool, Error>;
+    async fn complete(
+        &self,
+        idempotency_key: &str,
+        redirect_url: &str,
+        status: u16,
+    ) -> Result<(), Error>;
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct InMemoryOrderStore {
+    orders: Arc<Mutex<HashMap<String, StoredOrder>>>,
+}
+
+impl InMemoryOrderStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl OrderStore for InMemoryOrderStore {
+    async fn get(&self, idempotency_key: &str) -> Result<Option<StoredOrder>, Error> {
+        Ok(self.orders.lock().await.get(idempotency_key).cloned())
+    }
+
+    async fn insert(&self, order: StoredOrder) -> Result<bool, Error> {
+        let mut orders = self.orders.lock().await;
+        if orders.contains_key(&order.idempotency_key) {
+            return Ok(false);
+        }
+        orders.insert(order.idempotency_key.clone(), order);
+
+        Ok(true)
+    }
+
+    async fn complete(
+        &self,
+        idempotency_key: &str,
+        redirect_url: &str,
+        status: u16,
+    ) -> Result<(), Error> {
+        let mut orders = self.orders.lock().await;
+        let Some(order) = orders.get_mut(idempotency_key) else {
+            bail!("Order {} is not found", idempotency_key);
+        };
+        order.redirect_url = Some(redirect_url.to_string());
+        order.status = status;
+        order.updated_at = unix_micros()?;
+
+        Ok(())
+    }
+}
+
+/// Keeps all orders in a single JSON file which is rewritten on every change. The orders are
+/// read once on `open`, so the file must not be shared between processes.
+#[derive(Debug, Clone)]
+pub struct FileOrderStore {
+    path: PathBuf,
+    orders: Arc<Mutex<HashMap<String, StoredOrder>>>,
+}
+
+impl FileOrderStore {
+    pub async fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let orders = match tokio::fs::read(&path).await {
+            Ok(bytes) if bytes.is_empty() => HashMap::new(),
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            path,
+            orders: Arc::new(Mutex::new(orders)),
+        })
+    }
+
+    async fn save(&self, orders: &HashMap<String, StoredOrder>) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(orders)?;
+        let tmp_path = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, json).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OrderStore for FileOrderStore {
+    async fn get(&self, idempotency_key: &str) -> Result<Option<StoredOrder>, Error> {
+        Ok(self.orders.lock().await.get(idempotency_key).cloned())
+    }
+
+    async fn insert(&self, order: StoredOrder) -> Result<bool, Error> {
+        let mut orders = self.orders.lock().await;
+        if orders.contains_key(&order.idempotency_key) {
+            return Ok(false);
+        }
+        orders.insert(order.idempotency_key.clone(), order);
+        self.save(&orders).await?;
+
+        Ok(true)
+    }
+
+    async fn complete(
+        &self,
+        idempotency_key: &str,
+        redirect_url: &str,
+        status: u16,
+    ) -> Result<(), Error> {
+        let mut orders = self.orders.lock().await;
+        let Some(order) = orders.get_mut(idempotency_key) else {
+            bail!("Order {} is not found", idempotency_key);
+        };
+        order.redirect_url = Some(redirect_url.to_string());
+        order.status = status;
+        order.updated_at = unix_micros()?;
+        self.save(&orders).await?;
+
+        Ok(())
+    }
+}
+
+pub struct IdempotentBuyer<S: OrderStore> {
+    client: GateFiRestClient,
+    store: S,
+    id_generator: OrderIdGenerator,
+    /// Held for the whole call, so a concurrent call with the same key waits for the first one.
+    in_flight: std::sync::Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl<S: OrderStore> IdempotentBuyer<S> {
+    pub fn new(client: GateFiRestClient, store: S, id_generator: OrderIdGenerator) -> Self {
+        Self {
+            client,
+            store,
+            id_generator,
+            in_flight: Default::default(),
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn new_order_id(&self) -> String {
+        self.id_generator.generate()
+    }
+
+    /// Repeating a call with the same key returns the stored result instead of creating
+    /// a second order. An empty `order_custom_id` is filled by the generator.
+    pub async fn buy_asset(
+        &self,
+        idempotency_key: &str,
+        params: GateFiBuyAssetParams,
+    ) -> Result<GateFiBuyAssetResponse, Error> {
+        let lock = self.key_lock(idempotency_key);
+        let result = {
+            let _guard = lock.lock().await;
+            self.buy_asset_locked(idempotency_key, params).await
+        };
+        self.release_key_lock(idempotency_key, lock);
+
+        result
+    }
+
+    async fn buy_asset_locked(
+        &self,
+        idempotency_key: &str,
+        mut params: GateFiBuyAssetParams,
+    ) -> Result<GateFiBuyAssetResponse, Error> {
+        let order = match self.store.get(idempotency_key).await? {
+            Some(order) => order,
+            None => {
+                let now = unix_micros()?;
+                let mut stored_params = params.clone();
+                if stored_params.order_custom_id.is_empty() {
+                    stored_params.order_custom_id = self.id_generator.generate();
+                }
+                let order = StoredOrder {
+                    idempotency_key: idempotency_key.to_string(),
+                    params: stored_params,
+                    redirect_url: None,
+                    status: 0,
+                    created_at: now,
+                    updated_at: now,
+                };
+
+                // Another process sharing the store, e.g. through a database, got the key first;
+                // its order may still be in flight, so it must not be submitted again from here.
+                if !self.store.insert(order.clone()).await? {
+                    bail!(
+                        "Order with idempotency key {} is already in progress",
+                        idempotency_key
+                    );
+                }
+                order
+            }
+        };
+
+        if params.order_custom_id.is_empty() {
+            params.order_custom_id = order.params.order_custom_id.clone();
+        }
+        if params != order.params {
+            bail!(
+                "Idempotency key {} is already used for a different order",
+                idempotency_key
+            );
+        }
+
+        if let Some(redirect_url) = order.redirect_url {
+            return Ok(GateFiBuyAssetResponse {
+                redirect_url,
+                order_custom_id: order.params.order_custom_id,
+                status: order.status,
+                metadata: HashMap::new(),
+            });
+        }
+
+        // not completed yet: a retry after a failure reuses the stored order_custom_id
+        let response = self.client.buy_asset(order.params).await?;
+        self.store
+            .complete(idempotency_key, &response.redirect_url, response.status)
+            .await?;
+
+        Ok(response)
+    }
+}
+
+impl<S: OrderStore> IdempotentBuyer<S> {
+    fn key_lock(&self, idempotency_key: &str) -> Arc<Mutex<()>> {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .entry(idempotency_key.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn release_key_lock(&self, idempotency_key: &str, lock: Arc<Mutex<()>>) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // one reference is held by the map and one by this call
+        if Arc::strong_count(&lock) <= 2 {
+            in_flight.remove(idempotency_key);
+        }
+    }
+}
+
+fn unix_micros() -> Result<u64, Error> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::config::GateFiApiConfig;
+    use crate::rest::endpoints::RawResponse;
+    use crate::rest::transport::{GateFiTransport, PreparedRequest};
+    use reqwest::header::{HeaderMap, HeaderValue, LOCATION};
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct CountingTransport {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl GateFiTransport for CountingTransport {
+        async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let mut headers = HeaderMap::new();
+            let location = format!("https://onramp-sandbox.gatefi.com/order/{call}");
+            headers.insert(LOCATION, HeaderValue::from_str(&location).unwrap());
+
+            Ok(RawResponse {
+                status: 303,
+                url: request.url.clone(),
+                headers,
+                body: String::new(),
+            })
+        }
+    }
+
+    fn params(order_custom_id: &str, amount: &str) -> GateFiBuyAssetParams {
+        GateFiBuyAssetParams {
+            amount: amount.to_string(),
+            crypto: "BTC".to_string(),
+            fiat: "USD".to_string(),
+            order_custom_id: order_custom_id.to_string(),
+            payment_method: "BANKCARD".to_string(),
+            redirect_url: String::new(),
+            region: "US".to_string(),
+            wallet_address: "mjEcj2LA3vj1nDi8ZD3QMCs9kNqVk7Dpee".to_string(),
+            wallet_memo: None,
+            exchange_wallet: false,
+            partner_fee: None,
+        }
+    }
+
+    fn stored(key: &str) -> StoredOrder {
+        StoredOrder {
+            idempotency_key: key.to_string(),
+            params: params("order-1", "50"),
+            redirect_url: None,
+            status: 0,
+            created_at: 1,
+            updated_at: 1,
+        }
+    }
+
+    #[test]
+    fn generate_unique_ids() {
+        let generator = OrderIdGenerator::new("shop");
+        let first = generator.generate();
+        let second = generator.generate();
+
+        assert!(first.starts_with("shop-"));
+        assert!(first.ends_with("-0"));
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn stores_round_trip() {
+        let memory = InMemoryOrderStore::new();
+        assert!(memory.insert(stored("a")).await.unwrap());
+        assert!(!memory.insert(stored("a")).await.unwrap());
+        memory
+            .complete("a", "https://example.com", 303)
+            .await
+            .unwrap();
+        assert!(memory.get("a").await.unwrap().unwrap().is_completed());
+        assert!(memory
+            .complete("b", "https://example.com", 303)
+            .await
+            .is_err());
+
+        let path = std::env::temp_dir().join(format!(
+            "gatefi-orders-{}-{}.json",
+            std::process::id(),
+            unix_micros().unwrap()
+        ));
+        let file = FileOrderStore::open(&path).await.unwrap();
+        assert!(file.insert(stored("a")).await.unwrap());
+        file.complete("a", "https://example.com", 303)
+            .await
+            .unwrap();
+
+        let reopened = FileOrderStore::open(&path).await.unwrap();
+        let order = reopened.get("a").await.unwrap().unwrap();
+        assert_eq!(Some("https://example.com".to_string()), order.redirect_url);
+        assert_eq!(303, order.status);
+        assert_eq!(params("order-1", "50"), order.params);
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn idempotent_buy() {
+        let transport = Arc::new(CountingTransport::default());
+        let client = GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(transport.clone());
+        let buyer = Arc::new(IdempotentBuyer::new(
+            client,
+            InMemoryOrderStore::new(),
+            OrderIdGenerator::new("test"),
+        ));
+
+        let (first, second) = tokio::join!(
+            buyer.buy_asset("key", params("", "50")),
+            buyer.buy_asset("key", params("", "50"))
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(1, transport.calls.load(Ordering::SeqCst));
+        assert_eq!(first.redirect_url, second.redirect_url);
+        assert_eq!(first.order_custom_id, second.order_custom_id);
+
+        let replay = buyer
+            .buy_asset("key", params(&first.order_custom_id, "50"))
+            .await
+            .unwrap();
+        assert_eq!(first.redirect_url, replay.redirect_url);
+        assert_eq!(303, replay.status);
+        assert_eq!(1, transport.calls.load(Ordering::SeqCst));
+
+        assert!(buyer.buy_asset("key", params("", "60")).await.is_err());
+        assert!(buyer
+            .buy_asset("key", params("other-id", "50"))
+            .await
+            .is_err());
+        assert_eq!(1, transport.calls.load(Ordering::SeqCst));
+    }
+}
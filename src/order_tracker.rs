@@ -0,0 +1,353 @@
+use crate::rest::errors::Error;
+use crate::rest::explorer::{Network, TransactionLink};
+use crate::rest::models::{GateFiCallbackData, GateFiTransactionStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct OrderStatusChange {
+    pub status: GateFiTransactionStatus,
+    /// See `GateFiCallbackData::normalized_status`.
+    #[serde(default)]
+    pub raw_status: String,
+    pub received_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TrackedOrder {
+    pub transaction_id: String,
+    pub custom_order_id: String,
+    pub status: GateFiTransactionStatus,
+    pub transaction_hashes: Vec<String>,
+    pub fail_reason: Option<String>,
+    pub history: Vec<OrderStatusChange>,
+    /// Callbacks that arrived after a later status; they do not change `status` or `updated_at`.
+    #[serde(default)]
+    pub stale_callbacks: Vec<OrderStatusChange>,
+    pub last_callback: GateFiCallbackData,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl TrackedOrder {
+    pub fn is_terminal(&self) -> bool {
+        self.status.is_terminal()
+    }
+
+    pub fn explorer_links(&self, network: Network) -> Vec<TransactionLink> {
+        crate::rest::explorer::transaction_links(
+            &self.last_callback.crypto_currency,
+            &self.transaction_hashes,
+            network,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IngestOutcome {
+    Created(GateFiTransactionStatus),
+    Updated {
+        from: GateFiTransactionStatus,
+        to: GateFiTransactionStatus,
+    },
+    /// The same `transaction_id` + status was already ingested.
+    Duplicate,
+    /// The callback arrived after a later status and only its details were merged.
+    OutOfOrder {
+        current: GateFiTransactionStatus,
+        received: GateFiTransactionStatus,
+    },
+}
+
+/// Tracks orders from verified callbacks; see `webhook::GateFiWebhookVerifier`.
+#[derive(Debug, Default)]
+pub struct OrderTracker {
+    state: Mutex<OrderTrackerState>,
+}
+
+#[derive(Debug, Default)]
+struct OrderTrackerState {
+    orders: HashMap<String, TrackedOrder>,
+    /// Transaction id and normalized status of every ingested callback.
+    seen: HashSet<(String, String)>,
+}
+
+impl OrderTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_orders(orders: Vec<TrackedOrder>) -> Self {
+        let mut state = OrderTrackerState::default();
+
+        for order in orders {
+            for change in order.history.iter().chain(&order.stale_callbacks) {
+                // orders persisted before `raw_status` only know the parsed status
+                let raw_status = if change.raw_status.is_empty() {
+                    change.status.to_string()
+                } else {
+                    change.raw_status.clone()
+                };
+                state
+                    .seen
+                    .insert((order.transaction_id.clone(), raw_status));
+            }
+            state.orders.insert(order.transaction_id.clone(), order);
+        }
+
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    pub fn ingest(&self, callback: GateFiCallbackData) -> Result<IngestOutcome, Error> {
+        self.ingest_at(callback, SystemTime::now())
+    }
+
+    pub fn ingest_at(
+        &self,
+        callback: GateFiCallbackData,
+        received_at: SystemTime,
+    ) -> Result<IngestOutcome, Error> {
+        let status = callback.parsed_status();
+        let raw_status = callback.normalized_status();
+        let received_at = unix_micros(received_at)?;
+        let mut state = self.state.lock().unwrap();
+
+        if !state
+            .seen
+            .insert((callback.transaction_id.clone(), raw_status.clone()))
+        {
+            return Ok(IngestOutcome::Duplicate);
+        }
+
+        let change = OrderStatusChange {
+            status,
+            raw_status,
+            received_at,
+        };
+
+        let Some(order) = state.orders.get_mut(&callback.transaction_id) else {
+            let order = TrackedOrder {
+                transaction_id: callback.transaction_id.clone(),
+                custom_order_id: callback.custom_order_id.clone(),
+                status,
+                transaction_hashes: callback.transaction_hashes.clone().unwrap_or_default(),
+                fail_reason: non_empty(&callback.fail_reason),
+                history: vec![change],
+                stale_callbacks: Vec::new(),
+                last_callback: callback,
+                created_at: received_at,
+                updated_at: received_at,
+            };
+            state.orders.insert(order.transaction_id.clone(), order);
+
+            return Ok(IngestOutcome::Created(status));
+        };
+
+        for hash in callback.transaction_hashes.iter().flatten() {
+            if !hash.is_empty() && !order.transaction_hashes.contains(hash) {
+                order.transaction_hashes.push(hash.clone());
+            }
+        }
+        if let Some(fail_reason) = non_empty(&callback.fail_reason) {
+            order.fail_reason = Some(fail_reason);
+        }
+
+        let current = order.status;
+        if !can_transition(current, status) {
+            order.stale_callbacks.push(change);
+
+            return Ok(IngestOutcome::OutOfOrder {
+                current,
+                received: status,
+            });
+        }
+
+        order.history.push(change);
+        order.updated_at = received_at;
+        order.status = status;
+        order.last_callback = callback;
+
+        Ok(IngestOutcome::Updated {
+            from: current,
+            to: status,
+        })
+    }
+
+    pub fn get(&self, transaction_id: &str) -> Option<TrackedOrder> {
+        self.state
+            .lock()
+            .unwrap()
+            .orders
+            .get(transaction_id)
+            .cloned()
+    }
+
+    pub fn orders(&self) -> Vec<TrackedOrder> {
+        self.state
+            .lock()
+            .unwrap()
+            .orders
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Non-terminal orders without any status change for longer than `max_age`.
+    pub fn stuck_orders(&self, max_age: Duration) -> Result<Vec<TrackedOrder>, Error> {
+        self.stuck_orders_at(max_age, SystemTime::now())
+    }
+
+    pub fn stuck_orders_at(
+        &self,
+        max_age: Duration,
+        now: SystemTime,
+    ) -> Result<Vec<TrackedOrder>, Error> {
+        let deadline = unix_micros(now)?.saturating_sub(max_age.as_micros() as u64);
+        let mut orders: Vec<TrackedOrder> = self
+            .state
+            .lock()
+            .unwrap()
+            .orders
+            .values()
+            .filter(|order| !order.is_terminal() && order.updated_at < deadline)
+            .cloned()
+            .collect();
+        orders.sort_by_key(|order| order.updated_at);
+
+        Ok(orders)
+    }
+}
+
+fn can_transition(from: GateFiTransactionStatus, to: GateFiTransactionStatus) -> bool {
+    match (from.progress(), to.progress()) {
+        (Some(from_progress), Some(to_progress)) => {
+            !from.is_terminal() && to_progress > from_progress
+        }
+        // a known status always replaces an unknown one, never the other way round
+        (None, Some(_)) => true,
+        (_, None) => false,
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value.as_ref().filter(|value| !value.is_empty()).cloned()
+}
+
+fn unix_micros(time: SystemTime) -> Result<u64, Error> {
+    Ok(time.duration_since(UNIX_EPOCH)?.as_micros() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn callback(status: &str) -> GateFiCallbackData {
+        GateFiCallbackData {
+            crypto_amount: "0.00163967".to_string(),
+            crypto_currency: "BTC".to_string(),
+            custom_order_id: "order-1".to_string(),
+            destination_wallet: "mjEcj2LA3vj1nDi8ZD3QMCs9kNqVk7Dpee".to_string(),
+            destination_wallet_memo: None,
+            fiat_currency: "USD".to_string(),
+            fiat_amount: "50".to_string(),
+            tap_on_fee_amount: None,
+            tap_on_fee_currency: None,
+            transaction_hashes: None,
+            transaction_id: "cbd38c1b-721a-4fa5-948d-8a628073084b".to_string(),
+            status: status.to_string(),
+            fail_reason: None,
+            payment_method: None,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn ingest_duplicates_and_out_of_order() {
+        let tracker = OrderTracker::new();
+
+        let outcome = tracker.ingest(callback("created")).unwrap();
+        assert_eq!(
+            IngestOutcome::Created(GateFiTransactionStatus::Created),
+            outcome
+        );
+
+        let mut succeeded = callback("succeeded");
+        succeeded.transaction_hashes = Some(vec!["abc".to_string()]);
+        let outcome = tracker.ingest(succeeded.clone()).unwrap();
+        assert_eq!(
+            IngestOutcome::Updated {
+                from: GateFiTransactionStatus::Created,
+                to: GateFiTransactionStatus::Succeeded
+            },
+            outcome
+        );
+
+        assert_eq!(IngestOutcome::Duplicate, tracker.ingest(succeeded).unwrap());
+        assert!(matches!(
+            tracker.ingest(callback("init")).unwrap(),
+            IngestOutcome::OutOfOrder { .. }
+        ));
+
+        assert!("refunded".parse::<GateFiTransactionStatus>().is_err());
+        assert!(matches!(
+            tracker.ingest(callback("refunded")).unwrap(),
+            IngestOutcome::OutOfOrder {
+                received: GateFiTransactionStatus::Unknown,
+                ..
+            }
+        ));
+        // unknown statuses are told apart by the status GateFi sent
+        assert!(matches!(
+            tracker.ingest(callback("chargeback")).unwrap(),
+            IngestOutcome::OutOfOrder { .. }
+        ));
+        assert_eq!(
+            IngestOutcome::Duplicate,
+            tracker.ingest(callback("Refunded")).unwrap()
+        );
+
+        let order = tracker.get("cbd38c1b-721a-4fa5-948d-8a628073084b").unwrap();
+        assert_eq!(GateFiTransactionStatus::Succeeded, order.status);
+        assert_eq!(vec!["abc".to_string()], order.transaction_hashes);
+        assert_eq!(3, order.stale_callbacks.len());
+        let restored = OrderTracker::from_orders(vec![order.clone()]);
+        assert_eq!(
+            IngestOutcome::Duplicate,
+            restored.ingest(callback("chargeback")).unwrap()
+        );
+        // persisted history keeps the variant names
+        assert_eq!(
+            "\"Succeeded\"",
+            serde_json::to_string(&order.status).unwrap()
+        );
+    }
+
+    #[test]
+    fn stuck_orders() {
+        let tracker = OrderTracker::new();
+        let received_at = SystemTime::now() - Duration::from_secs(3600);
+        tracker.ingest_at(callback("created"), received_at).unwrap();
+        // a late `init` must not reset the stuck timer
+        assert!(matches!(
+            tracker.ingest(callback("init")).unwrap(),
+            IngestOutcome::OutOfOrder { .. }
+        ));
+        let order = tracker.get("cbd38c1b-721a-4fa5-948d-8a628073084b").unwrap();
+        assert_eq!(1, order.history.len());
+        assert_eq!(1, order.stale_callbacks.len());
+
+        let stuck = tracker
+            .stuck_orders_at(Duration::from_secs(600), SystemTime::now())
+            .unwrap();
+        assert_eq!(1, stuck.len());
+
+        let stuck = tracker
+            .stuck_orders_at(Duration::from_secs(7200), SystemTime::now())
+            .unwrap();
+        assert!(stuck.is_empty());
+    }
+}
@@ -1,24 +1,34 @@
+use crate::rest::config::{GateFiConfig, PROD_PAYMENT_PAGE_HOST, SANDBOX_PAYMENT_PAGE_HOST};
+use crate::rest::currency::Currency;
+use crate::rest::errors::Error;
+use crate::rest::partner_fee::{PartnerFee, PartnerFeeConfig, PartnerFeeParams};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PaymentPage {
-    host: &'static str,
+    host: String,
     config: PaymentPageConfig,
 }
 
 impl PaymentPage {
-    pub fn new_sandbox(config: PaymentPageConfig) -> Self {
+    pub fn new(host: impl Into<String>, config: PaymentPageConfig) -> Self {
         Self {
-            host: "https://onramp-sandbox.gatefi.com",
+            host: host.into(),
             config,
         }
     }
 
+    pub fn new_sandbox(config: PaymentPageConfig) -> Self {
+        Self::new(SANDBOX_PAYMENT_PAGE_HOST, config)
+    }
+
     pub fn new_prod(config: PaymentPageConfig) -> Self {
-        Self {
-            host: "https://onramp.gatefi.com",
-            config,
-        }
+        Self::new(PROD_PAYMENT_PAGE_HOST, config)
+    }
+
+    pub fn from_config(gatefi_config: &GateFiConfig, config: PaymentPageConfig) -> Self {
+        Self::new(gatefi_config.payment_page_host(), config)
     }
 
     pub fn into_url(self) -> String {
@@ -33,6 +43,13 @@ pub struct PaymentPageConfig {
     pub wallet: String,
     #[serde(rename = "walletLock")]
     pub wallet_lock: bool,
+    /// The `walletMemo` name is not confirmed by GateFi yet.
+    #[serde(
+        rename = "walletMemo",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub wallet_memo: Option<String>,
     #[serde(rename = "fiatCurrency")]
     pub fiat_currency: String,
     #[serde(rename = "fiatCurrencyLock")]
@@ -47,4 +64,46 @@ pub struct PaymentPageConfig {
     pub external_id: String,
     #[serde(rename = "partnerAccountId")]
     pub partner_account_id: String,
+    /// Named as in `PartnerFeeParams`.
+    #[serde(flatten, default)]
+    pub partner_fee_params: BTreeMap<String, String>,
+    /// Not sent; an exchange deposit wallet needs a memo where the chain requires one.
+    #[serde(skip)]
+    pub exchange_wallet: bool,
+}
+
+impl PaymentPageConfig {
+    /// Sets a destination tag or memo, validated against `crypto_currency`.
+    pub fn with_wallet_memo(mut self, memo: impl Into<String>) -> Result<Self, Error> {
+        let memo = memo.into();
+        Currency::validate_memo(&self.crypto_currency, Some(&memo), self.exchange_wallet)?;
+        self.wallet_memo = Some(memo);
+
+        Ok(self)
+    }
+
+    /// Checks `wallet_memo` against `crypto_currency`, requiring one for an `exchange_wallet`.
+    pub fn validate(&self) -> Result<(), Error> {
+        Currency::validate_memo(
+            &self.crypto_currency,
+            self.wallet_memo.as_deref(),
+            self.exchange_wallet,
+        )
+    }
+
+    pub fn with_partner_fee(mut self, fee: &PartnerFee, params: &PartnerFeeParams) -> Self {
+        self.partner_fee_params = fee.params(params);
+        self
+    }
+
+    /// Applies the fee configured for `crypto_currency`, if any and if `params` are set.
+    pub fn with_partner_fees(self, fees: &PartnerFeeConfig) -> Self {
+        match (fees.resolve(&self.crypto_currency, None), &fees.params) {
+            (Some(fee), Some(params)) => {
+                let fee = fee.clone();
+                self.with_partner_fee(&fee, params)
+            }
+            _ => self,
+        }
+    }
 }
@@ -0,0 +1,481 @@
+use crate::payment_page::PaymentPageConfig;
+use crate::rest::currency::{Currency, CURRENCIES};
+use crate::rest::errors::Error;
+use crate::rest::explorer::Network;
+use crate::rest::rest_client::GateFiBuyAssetParams;
+use error_chain::bail;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Wallet uri schemes as `(scheme, blockchain, native currency id)`.
+const URI_SCHEMES: &[(&str, &str, Option<&str>)] = &[
+    ("bitcoin", "BTC", Some("BTC")),
+    ("litecoin", "LTC", Some("LTC")),
+    ("bitcoincash", "BCH", Some("BCH")),
+    ("dogecoin", "DOGE", Some("DOGE")),
+    ("cardano", "ADA", Some("ADA")),
+    ("web+cardano", "ADA", Some("ADA")),
+    ("ripple", "XRP", Some("XRP")),
+    ("xrpl", "XRP", Some("XRP")),
+    ("xrp", "XRP", Some("XRP")),
+    ("bnb", "BEP2", Some("BNB")),
+    ("binance", "BEP2", Some("BNB")),
+    ("tron", "TRC20", None),
+];
+
+/// EVM chain ids as `(chain id, blockchain, native currency id)`.
+const EVM_CHAINS: &[(u64, &str, Option<&str>)] = &[
+    (1, "ERC20", None),
+    (11155111, "ERC20", None),
+    (56, "BEP20", Some("BNB-BSC")),
+    (97, "BEP20", Some("BNB-BSC")),
+];
+
+const EVM_NATIVE_DECIMALS: u32 = 18;
+
+/// Address prefixes telling networks apart as `(blockchain, prefix, network)`; bech32
+/// prefixes ending in `1` match in any case.
+const ADDRESS_PREFIXES: &[(&str, &str, Network)] = &[
+    ("BTC", "bc1", Network::Mainnet),
+    ("BTC", "1", Network::Mainnet),
+    ("BTC", "3", Network::Mainnet),
+    ("BTC", "tb1", Network::Testnet),
+    ("BTC", "bcrt1", Network::Testnet),
+    ("BTC", "m", Network::Testnet),
+    ("BTC", "n", Network::Testnet),
+    ("BTC", "2", Network::Testnet),
+    ("LTC", "ltc1", Network::Mainnet),
+    ("LTC", "L", Network::Mainnet),
+    ("LTC", "M", Network::Mainnet),
+    ("LTC", "tltc1", Network::Testnet),
+    ("DOGE", "D", Network::Mainnet),
+    ("DOGE", "n", Network::Testnet),
+];
+
+/// Query keys wallets use for XRP destination tags and BEP2 memos.
+const MEMO_KEYS: &[&str] = &["dt", "tag", "memo", "destinationtag"];
+
+pub struct TokenContract {
+    pub blockchain: &'static str,
+    pub contract: &'static str,
+    pub currency: &'static str,
+    pub decimals: u32,
+}
+
+/// Mainnet contracts of the tokens in `CURRENCIES`.
+pub const TOKEN_CONTRACTS: &[TokenContract] = &[
+    TokenContract {
+        blockchain: "ERC20",
+        contract: "0xdAC17F958D2ee523a2206206994597C13D831ec7",
+        currency: "USDTE",
+        decimals: 6,
+    },
+    TokenContract {
+        blockchain: "ERC20",
+        contract: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+        currency: "USDC",
+        decimals: 6,
+    },
+    TokenContract {
+        blockchain: "ERC20",
+        contract: "0xdB25f211AB05b1c97D595516F45794528a807ad8",
+        currency: "EURS",
+        decimals: 2,
+    },
+    TokenContract {
+        blockchain: "TRC20",
+        contract: "TR7NHqjeKQxGTCi8q8ZY4pL8otSzgjLj6t",
+        currency: "USDTT",
+        decimals: 6,
+    },
+];
+
+/// A parsed BIP-21, EIP-681 or similar wallet uri.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PaymentUri {
+    pub scheme: String,
+    pub address: String,
+    pub blockchain: String,
+    /// Currency id in `CURRENCIES`, `None` when the asset is not supported, e.g. plain ETH.
+    pub currency: Option<String>,
+    /// EVM chain id, defaults to 1 as in EIP-681.
+    pub chain_id: Option<u64>,
+    /// Crypto amount in whole units.
+    pub amount: Option<Decimal>,
+    pub memo: Option<String>,
+    pub label: Option<String>,
+}
+
+impl PaymentUri {
+    pub fn parse(uri: &str) -> Result<Self, Error> {
+        let uri = uri.trim();
+        let Some((scheme, rest)) = uri.split_once(':') else {
+            bail!("Invalid payment uri {}, missing scheme", uri);
+        };
+        let scheme = scheme.to_lowercase();
+        let rest = rest.trim_start_matches("//");
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let params: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+            .map(|(key, value)| (key.to_lowercase(), value.into_owned()))
+            .collect();
+
+        if scheme == "ethereum" {
+            return Self::parse_eip681(scheme, path, params);
+        }
+
+        let Some((_, blockchain, native)) =
+            URI_SCHEMES.iter().find(|(known, _, _)| *known == scheme)
+        else {
+            bail!("Unsupported payment uri scheme {}", scheme);
+        };
+        // BIP-21 requires rejecting uris with unknown `req-` parameters.
+        if let Some(key) = params.keys().find(|key| key.starts_with("req-")) {
+            bail!("Unsupported required parameter {} in payment uri", key);
+        }
+        let currency = match params.get("token") {
+            Some(token) => match find_token(blockchain, token) {
+                Some(currency) => Some(currency),
+                None => bail!("Unknown {} token {}", blockchain, token),
+            },
+            None => native.map(str::to_string),
+        };
+
+        Ok(Self {
+            scheme,
+            address: non_empty_address(path)?,
+            blockchain: blockchain.to_string(),
+            currency,
+            chain_id: None,
+            amount: params
+                .get("amount")
+                .map(|amount| parse_amount(amount, 0))
+                .transpose()?,
+            memo: memo(&params),
+            label: params.get("label").cloned(),
+        })
+    }
+
+    /// `ethereum:[pay-]<address>[@chain_id][/transfer]?...` with `value` in wei, or
+    /// `address` and `uint256` in token base units for transfers.
+    fn parse_eip681(
+        scheme: String,
+        path: &str,
+        params: HashMap<String, String>,
+    ) -> Result<Self, Error> {
+        let path = path.strip_prefix("pay-").unwrap_or(path);
+        let (target, function) = match path.split_once('/') {
+            Some((target, function)) => (target, Some(function)),
+            None => (path, None),
+        };
+        let (target, chain_id) = match target.split_once('@') {
+            Some((target, chain_id)) => match chain_id.parse::<u64>() {
+                Ok(chain_id) => (target, chain_id),
+                Err(_) => bail!("Invalid chain id {} in payment uri", chain_id),
+            },
+            None => (target, 1),
+        };
+        let Some((_, blockchain, native)) = EVM_CHAINS.iter().find(|(id, _, _)| *id == chain_id)
+        else {
+            bail!("Unsupported chain id {} in payment uri", chain_id);
+        };
+
+        let (address, currency, amount) = match function {
+            Some("transfer") => {
+                let Some(recipient) = params.get("address") else {
+                    bail!("Missing transfer address in payment uri");
+                };
+                let token = TOKEN_CONTRACTS.iter().find(|token| {
+                    token.blockchain == *blockchain && token.contract.eq_ignore_ascii_case(target)
+                });
+                let amount = match (params.get("uint256"), token) {
+                    (Some(amount), Some(token)) => Some(parse_amount(amount, token.decimals)?),
+                    (Some(_), None) => bail!("Unknown token contract {}", target),
+                    (None, _) => None,
+                };
+                (
+                    recipient.as_str(),
+                    token.map(|token| token.currency.to_string()),
+                    amount,
+                )
+            }
+            Some(function) => bail!("Unsupported payment uri function {}", function),
+            None => {
+                let amount = match (params.get("value"), params.get("amount")) {
+                    (Some(value), _) => Some(parse_amount(value, EVM_NATIVE_DECIMALS)?),
+                    (None, Some(amount)) => Some(parse_amount(amount, 0)?),
+                    (None, None) => None,
+                };
+                (target, native.map(str::to_string), amount)
+            }
+        };
+
+        Ok(Self {
+            scheme,
+            address: non_empty_address(address)?,
+            blockchain: blockchain.to_string(),
+            currency,
+            chain_id: Some(chain_id),
+            amount,
+            memo: None,
+            label: params.get("label").cloned(),
+        })
+    }
+
+    pub fn currency(&self) -> Option<&'static Currency> {
+        self.currency.as_deref().and_then(Currency::find)
+    }
+
+    /// From the chain id of EVM uris and from known address prefixes on BTC, LTC and DOGE.
+    /// Other addresses, e.g. XRP, look the same on every network and give `None`.
+    pub fn network(&self) -> Option<Network> {
+        if let Some(chain_id) = self.chain_id {
+            return Network::from_chain_id(&chain_id.to_string());
+        }
+        let lowercase = self.address.to_lowercase();
+
+        ADDRESS_PREFIXES
+            .iter()
+            .find(|(blockchain, prefix, _)| {
+                *blockchain == self.blockchain
+                    && (self.address.starts_with(prefix)
+                        || (prefix.len() > 1
+                            && prefix.ends_with('1')
+                            && lowercase.starts_with(prefix)))
+            })
+            .map(|(_, _, network)| *network)
+    }
+
+    /// Sets crypto, wallet and memo. The uri amount is in crypto while GateFi takes fiat
+    /// amounts, so `params.amount` is kept and the uri amount is returned to be quoted.
+    ///
+    /// `network` is the one orders settle on, e.g. `Network::from(config.environment)`. Currency
+    /// ids do not tell networks apart, so a uri for another network is rejected.
+    pub fn apply_to_buy_params(
+        &self,
+        mut params: GateFiBuyAssetParams,
+        network: Network,
+    ) -> Result<(GateFiBuyAssetParams, Option<Decimal>), Error> {
+        let currency = self.supported_currency(network)?;
+        params.crypto = currency.id.to_string();
+        params.wallet_address = self.address.clone();
+        params.wallet_memo = self.memo.clone();
+
+        Ok((params, self.amount))
+    }
+
+    /// As `apply_to_buy_params`, returning the uri amount in crypto.
+    pub fn apply_to_payment_page(
+        &self,
+        mut config: PaymentPageConfig,
+        network: Network,
+    ) -> Result<(PaymentPageConfig, Option<Decimal>), Error> {
+        let currency = self.supported_currency(network)?;
+        config.crypto_currency = currency.id.to_string();
+        config.wallet = self.address.clone();
+        config.wallet_memo = self.memo.clone();
+        config.validate()?;
+
+        Ok((config, self.amount))
+    }
+
+    fn supported_currency(&self, network: Network) -> Result<&'static Currency, Error> {
+        if let Some(uri_network) = self.network().filter(|uri_network| *uri_network != network) {
+            bail!(
+                "Payment uri {} is for {:?}, orders settle on {:?}",
+                self.address,
+                uri_network,
+                network
+            );
+        }
+        let Some(currency) = self.currency() else {
+            bail!(
+                "Payment uri asset on {} is not a supported currency",
+                self.blockchain
+            );
+        };
+        Currency::validate_memo(currency.id, self.memo.as_deref(), false)?;
+
+        Ok(currency)
+    }
+}
+
+impl FromStr for PaymentUri {
+    type Err = Error;
+
+    fn from_str(uri: &str) -> Result<Self, Self::Err> {
+        Self::parse(uri)
+    }
+}
+
+fn find_token(blockchain: &str, token: &str) -> Option<String> {
+    if let Some(contract) = TOKEN_CONTRACTS
+        .iter()
+        .find(|contract| contract.blockchain == blockchain && contract.contract == token)
+    {
+        return Some(contract.currency.to_string());
+    }
+
+    CURRENCIES
+        .iter()
+        .find(|currency| {
+            currency.blockchain == blockchain && currency.symbol.eq_ignore_ascii_case(token)
+        })
+        .map(|currency| currency.id.to_string())
+}
+
+fn memo(params: &HashMap<String, String>) -> Option<String> {
+    MEMO_KEYS
+        .iter()
+        .find_map(|key| params.get(*key))
+        .filter(|memo| !memo.is_empty())
+        .cloned()
+}
+
+fn non_empty_address(address: &str) -> Result<String, Error> {
+    let address = address.trim();
+    if address.is_empty() {
+        bail!("Payment uri has no address");
+    }
+
+    Ok(address.to_string())
+}
+
+/// Parses plain or scientific amounts, e.g. `2.014e18` wei, given in `10^-decimals` units.
+fn parse_amount(amount: &str, decimals: u32) -> Result<Decimal, Error> {
+    let parsed = Decimal::from_str(amount).or_else(|_| Decimal::from_scientific(amount));
+    match parsed {
+        Ok(parsed) if parsed.is_sign_negative() => bail!("Negative payment uri amount {}", amount),
+        Ok(parsed) => Ok((parsed * Decimal::new(1, decimals)).normalize()),
+        Err(err) => bail!("Invalid payment uri amount {}: {}", amount, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payment_page::PaymentPage;
+
+    #[test]
+    fn parse_payment_uris() {
+        let btc = PaymentUri::parse(
+            "BITCOIN:bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq?amount=0.0015&label=Deposit%201",
+        )
+        .unwrap();
+        assert_eq!(Some("BTC"), btc.currency().map(|currency| currency.id));
+        assert_eq!(Some(Decimal::new(15, 4)), btc.amount);
+        assert_eq!(Some("Deposit 1".to_string()), btc.label);
+        assert!(PaymentUri::parse("bitcoin:bc1q?req-somethingnew=1").is_err());
+
+        let xrp = PaymentUri::parse("ripple:rPEPPER7kfTD9w2To4CQk6UCfuHM9c6GDY?dt=12345").unwrap();
+        assert_eq!(Some("12345".to_string()), xrp.memo);
+
+        let bnb = PaymentUri::parse(
+            "ethereum:pay-0x89205A3A3b2A69De6Dbf7f01ED13B2108B2c43e7@56?value=2.014e18",
+        )
+        .unwrap();
+        assert_eq!(Some("BNB-BSC".to_string()), bnb.currency);
+        assert_eq!(Some(Decimal::new(2014, 3)), bnb.amount);
+        assert_eq!(Some(Network::Mainnet), bnb.network());
+
+        let usdc = PaymentUri::parse(
+            "ethereum:0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48/transfer?address=0x89205A3A3b2A69De6Dbf7f01ED13B2108B2c43e7&uint256=2500000",
+        )
+        .unwrap();
+        assert_eq!(Some(Decimal::new(25, 1)), usdc.amount);
+
+        let params = GateFiBuyAssetParams {
+            amount: "100".to_string(),
+            crypto: String::new(),
+            fiat: "EUR".to_string(),
+            order_custom_id: String::new(),
+            payment_method: "BANKCARD".to_string(),
+            redirect_url: String::new(),
+            region: "US".to_string(),
+            wallet_address: String::new(),
+            wallet_memo: None,
+            exchange_wallet: false,
+            partner_fee: None,
+        };
+        let (applied, amount) = usdc
+            .apply_to_buy_params(params.clone(), Network::Mainnet)
+            .unwrap();
+        assert_eq!("USDC", applied.crypto);
+        assert_eq!("100", applied.amount);
+        assert_eq!(Some(Decimal::new(25, 1)), amount);
+        assert_eq!(
+            "0x89205A3A3b2A69De6Dbf7f01ED13B2108B2c43e7",
+            applied.wallet_address
+        );
+        assert!(usdc
+            .apply_to_buy_params(params.clone(), Network::Testnet)
+            .is_err());
+
+        // BSC testnet maps to the mainnet currency ids, so it only fits sandbox orders
+        let bsc_testnet =
+            PaymentUri::parse("ethereum:0x89205A3A3b2A69De6Dbf7f01ED13B2108B2c43e7@97?value=1e18")
+                .unwrap();
+        assert_eq!(Some(Network::Testnet), bsc_testnet.network());
+        assert!(bsc_testnet
+            .apply_to_buy_params(params.clone(), Network::Mainnet)
+            .is_err());
+        assert!(bsc_testnet
+            .apply_to_buy_params(params.clone(), Network::Testnet)
+            .is_ok());
+
+        let btc_testnet =
+            PaymentUri::parse("bitcoin:TB1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KXPJZSX").unwrap();
+        assert_eq!(Some(Network::Testnet), btc_testnet.network());
+        assert!(btc_testnet
+            .apply_to_buy_params(params.clone(), Network::Mainnet)
+            .is_err());
+        assert_eq!(Some(Network::Mainnet), btc.network());
+        assert_eq!(None, xrp.network());
+        assert!(xrp.apply_to_buy_params(params, Network::Testnet).is_ok());
+
+        let eth = PaymentUri::parse("ethereum:0x89205A3A3b2A69De6Dbf7f01ED13B2108B2c43e7").unwrap();
+        assert!(eth.currency.is_none());
+    }
+
+    #[test]
+    fn apply_memo() {
+        let xrp = PaymentUri::parse("xrpl:rPEPPER7kfTD9w2To4CQk6UCfuHM9c6GDY?dt=12345").unwrap();
+        let params = GateFiBuyAssetParams {
+            amount: "100".to_string(),
+            crypto: String::new(),
+            fiat: "EUR".to_string(),
+            order_custom_id: "order-1".to_string(),
+            payment_method: "BANKCARD".to_string(),
+            redirect_url: String::new(),
+            region: "US".to_string(),
+            wallet_address: String::new(),
+            wallet_memo: None,
+            exchange_wallet: true,
+            partner_fee: None,
+        };
+        let (params, _) = xrp.apply_to_buy_params(params, Network::Mainnet).unwrap();
+        assert_eq!("XRP", params.crypto);
+        assert_eq!(Some("12345".to_string()), params.wallet_memo);
+
+        let config = PaymentPageConfig {
+            wallet: String::new(),
+            wallet_lock: true,
+            wallet_memo: None,
+            fiat_currency: "EUR".to_string(),
+            fiat_currency_lock: true,
+            fiat_amount: "100".to_string(),
+            fiat_amount_lock: true,
+            crypto_currency: "XRP".to_string(),
+            external_id: "order-1".to_string(),
+            partner_account_id: "partner".to_string(),
+            partner_fee_params: Default::default(),
+            exchange_wallet: true,
+        };
+        assert!(config.validate().is_err());
+        let (config, _) = xrp.apply_to_payment_page(config, Network::Mainnet).unwrap();
+        assert!(PaymentPage::new_sandbox(config)
+            .into_url()
+            .contains("&walletMemo=12345&"));
+    }
+}
@@ -0,0 +1,306 @@
+use crate::rest::errors::Error;
+use crate::rest::models::GateFiRatesResponse;
+use crate::rest::rest_client::GateFiRestClient;
+use error_chain::bail;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+
+pub const DEFAULT_PIVOTS: &[&str] = &["USD", "EUR", "USDT", "BTC"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLegKind {
+    Direct,
+    Inverse,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLeg {
+    pub from: String,
+    pub to: String,
+    pub rate: f64,
+    pub kind: RateLegKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateConversion {
+    pub from: String,
+    pub to: String,
+    pub amount: f64,
+    pub converted_amount: f64,
+    pub rate: f64,
+    /// Empty when `from` and `to` are the same asset.
+    pub path: Vec<RateLeg>,
+}
+
+impl RateConversion {
+    pub fn pivot(&self) -> Option<&str> {
+        if self.path.len() > 1 {
+            Some(self.path[0].to.as_str())
+        } else {
+            None
+        }
+    }
+}
+
+/// Snapshot of `get_rates`, where `list[from].rates[to]` is the amount of `to` for one `from`.
+#[derive(Debug, Clone)]
+pub struct RateBook {
+    rates: HashMap<String, HashMap<String, f64>>,
+    pivots: Vec<String>,
+    snapshot_time: SystemTime,
+}
+
+impl RateBook {
+    pub fn new(response: GateFiRatesResponse) -> Self {
+        Self::with_snapshot_time(response, SystemTime::now())
+    }
+
+    pub fn with_snapshot_time(response: GateFiRatesResponse, snapshot_time: SystemTime) -> Self {
+        let rates = response
+            .list
+            .into_iter()
+            .map(|(from, rates)| (from.to_uppercase(), normalize(rates.rates)))
+            .collect();
+
+        Self {
+            rates,
+            pivots: DEFAULT_PIVOTS.iter().map(|p| p.to_string()).collect(),
+            snapshot_time,
+        }
+    }
+
+    pub fn with_pivots(mut self, pivots: Vec<String>) -> Self {
+        self.pivots = pivots.into_iter().map(|p| p.to_uppercase()).collect();
+        self
+    }
+
+    pub async fn load(client: &GateFiRestClient) -> Result<Self, Error> {
+        let response = client.get_rates().await?;
+
+        Ok(Self::new(response))
+    }
+
+    pub fn snapshot_time(&self) -> SystemTime {
+        self.snapshot_time
+    }
+
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.snapshot_time)
+            .unwrap_or_default()
+    }
+
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        self.age() > max_age
+    }
+
+    pub fn assets(&self) -> Vec<String> {
+        let mut assets: Vec<String> = self
+            .rates
+            .iter()
+            .flat_map(|(from, rates)| std::iter::once(from.clone()).chain(rates.keys().cloned()))
+            .collect();
+        assets.sort();
+        assets.dedup();
+
+        assets
+    }
+
+    pub fn rate(&self, from: &str, to: &str) -> Result<f64, Error> {
+        Ok(self.convert(1.0, from, to)?.rate)
+    }
+
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Result<RateConversion, Error> {
+        let from = from.to_uppercase();
+        let to = to.to_uppercase();
+
+        let path = if from == to {
+            Vec::new()
+        } else if let Some(leg) = self.find_leg(&from, &to) {
+            vec![leg]
+        } else {
+            let Some(path) = self.find_pivot_path(&from, &to) else {
+                bail!("No rate found to convert {} to {}", from, to);
+            };
+            path
+        };
+        let rate = path.iter().map(|leg| leg.rate).product::<f64>();
+
+        Ok(RateConversion {
+            from,
+            to,
+            amount,
+            converted_amount: amount * rate,
+            rate,
+            path,
+        })
+    }
+
+    fn find_pivot_path(&self, from: &str, to: &str) -> Option<Vec<RateLeg>> {
+        self.pivots
+            .iter()
+            .filter(|pivot| pivot.as_str() != from && pivot.as_str() != to)
+            .find_map(|pivot| {
+                let first = self.find_leg(from, pivot)?;
+                let second = self.find_leg(pivot, to)?;
+                Some(vec![first, second])
+            })
+    }
+
+    fn find_leg(&self, from: &str, to: &str) -> Option<RateLeg> {
+        if let Some(rate) = self.direct_rate(from, to) {
+            return Some(RateLeg {
+                from: from.to_string(),
+                to: to.to_string(),
+                rate,
+                kind: RateLegKind::Direct,
+            });
+        }
+
+        self.direct_rate(to, from).map(|rate| RateLeg {
+            from: from.to_string(),
+            to: to.to_string(),
+            rate: 1.0 / rate,
+            kind: RateLegKind::Inverse,
+        })
+    }
+
+    fn direct_rate(&self, from: &str, to: &str) -> Option<f64> {
+        self.rates
+            .get(from)
+            .and_then(|rates| rates.get(to))
+            .copied()
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+    }
+}
+
+fn normalize(rates: HashMap<String, f64>) -> HashMap<String, f64> {
+    rates
+        .into_iter()
+        .map(|(to, rate)| (to.to_uppercase(), rate))
+        .collect()
+}
+
+#[derive(Clone, Default)]
+pub struct SharedRateBook {
+    inner: Arc<RwLock<SharedRateBookState>>,
+    pivots: Option<Vec<String>>,
+}
+
+#[derive(Default)]
+struct SharedRateBookState {
+    book: Option<Arc<RateBook>>,
+    last_error: Option<String>,
+}
+
+impl SharedRateBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pivots(mut self, pivots: Vec<String>) -> Self {
+        self.pivots = Some(pivots);
+        self
+    }
+
+    pub fn get(&self) -> Option<Arc<RateBook>> {
+        self.inner.read().unwrap().book.clone()
+    }
+
+    pub fn get_fresh(&self, max_age: Duration) -> Result<Arc<RateBook>, Error> {
+        let Some(book) = self.get() else {
+            bail!("Rates are not loaded yet");
+        };
+
+        if book.is_stale(max_age) {
+            bail!(
+                "Rates are stale: snapshot is {}s old, max age {}s",
+                book.age().as_secs(),
+                max_age.as_secs()
+            );
+        }
+
+        Ok(book)
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.inner.read().unwrap().last_error.clone()
+    }
+
+    pub fn set(&self, book: RateBook) {
+        let book = match &self.pivots {
+            Some(pivots) => book.with_pivots(pivots.clone()),
+            None => book,
+        };
+        let mut state = self.inner.write().unwrap();
+        state.book = Some(Arc::new(book));
+        state.last_error = None;
+    }
+
+    pub async fn refresh(&self, client: &GateFiRestClient) -> Result<(), Error> {
+        match RateBook::load(client).await {
+            Ok(book) => {
+                self.set(book);
+                Ok(())
+            }
+            Err(err) => {
+                self.inner.write().unwrap().last_error = Some(err.to_string());
+                Err(err)
+            }
+        }
+    }
+
+    /// Keeps the previous snapshot when a refresh fails; use `get_fresh` to detect staleness.
+    pub fn spawn_refresh(&self, client: GateFiRestClient, interval: Duration) -> JoinHandle<()> {
+        let book = self.clone();
+
+        tokio::spawn(async move {
+            let mut timer = tokio::time::interval(interval);
+            loop {
+                timer.tick().await;
+                let _ = book.refresh(&client).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates() -> GateFiRatesResponse {
+        let json = r#"{"list":{"BTC":{"rates":{"USD":50000.0}},"EUR":{"rates":{"USD":1.25}}}}"#;
+
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn convert_direct_inverse_and_pivot() {
+        let book = RateBook::new(rates());
+
+        let direct = book.convert(2.0, "BTC", "USD").unwrap();
+        assert_eq!(100000.0, direct.converted_amount);
+        assert_eq!(RateLegKind::Direct, direct.path[0].kind);
+
+        let inverse = book.convert(100.0, "usd", "eur").unwrap();
+        assert_eq!(80.0, inverse.converted_amount);
+        assert_eq!(RateLegKind::Inverse, inverse.path[0].kind);
+
+        let pivot = book.convert(1.0, "BTC", "EUR").unwrap();
+        assert_eq!(40000.0, pivot.converted_amount);
+        assert_eq!(Some("USD"), pivot.pivot());
+
+        assert!(book.convert(1.0, "BTC", "MXN").is_err());
+    }
+
+    #[test]
+    fn stale_snapshot() {
+        let time = SystemTime::now() - Duration::from_secs(120);
+        let book = RateBook::with_snapshot_time(rates(), time);
+
+        assert!(book.is_stale(Duration::from_secs(60)));
+        assert!(!book.is_stale(Duration::from_secs(600)));
+    }
+}
@@ -0,0 +1,400 @@
+use crate::order_tracker::TrackedOrder;
+use crate::rate_book::RateBook;
+use crate::rest::errors::Error;
+use crate::rest::models::{GateFiCallbackData, GateFiTransactionStatus};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::str::FromStr;
+
+const MICROS_PER_DAY: u64 = 86_400_000_000;
+const UNKNOWN_METHOD: &str = "UNKNOWN";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    Day,
+    Month,
+    Year,
+}
+
+impl ReportPeriod {
+    /// Formats a unix time in microseconds as `2024-05-17`, `2024-05` or `2024` (UTC).
+    pub fn key(&self, unix_micros: u64) -> String {
+        let (year, month, day) = civil_from_days((unix_micros / MICROS_PER_DAY) as i64);
+
+        match self {
+            ReportPeriod::Day => format!("{year:04}-{month:02}-{day:02}"),
+            ReportPeriod::Month => format!("{year:04}-{month:02}"),
+            ReportPeriod::Year => format!("{year:04}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReportEntry {
+    /// Unix time in microseconds used to pick the period.
+    pub received_at: u64,
+    pub callback: GateFiCallbackData,
+}
+
+impl From<&TrackedOrder> for ReportEntry {
+    fn from(order: &TrackedOrder) -> Self {
+        Self {
+            received_at: order.created_at,
+            callback: order.last_callback.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RevenueReportRow {
+    pub period: String,
+    pub fiat: String,
+    pub crypto: String,
+    pub payment_method: String,
+    pub status: GateFiTransactionStatus,
+    /// See `GateFiCallbackData::normalized_status`.
+    pub raw_status: String,
+    pub count: u64,
+    pub fiat_volume: Decimal,
+    pub crypto_volume: Decimal,
+    /// Tap-on fees in `fiat`.
+    pub tap_on_fees: Decimal,
+    pub fiat_volume_base: Option<Decimal>,
+    pub tap_on_fees_base: Option<Decimal>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RevenueReport {
+    pub period: ReportPeriod,
+    pub base_currency: Option<String>,
+    pub rows: Vec<RevenueReportRow>,
+    /// Currency pairs without a rate; affected amounts are left out of base totals or fees.
+    pub missing_rates: Vec<String>,
+    /// Transactions with unparsable amounts or status.
+    pub skipped: Vec<String>,
+}
+
+impl RevenueReport {
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "period,fiat,crypto,payment_method,status,count,fiat_volume,crypto_volume,tap_on_fees,base_currency,fiat_volume_base,tap_on_fees_base\n",
+        );
+        let base_currency = self.base_currency.as_deref().unwrap_or("");
+        let optional = |value: Option<Decimal>| value.map(|v| v.to_string()).unwrap_or_default();
+
+        for row in &self.rows {
+            let fields = [
+                row.period.clone(),
+                row.fiat.clone(),
+                row.crypto.clone(),
+                row.payment_method.clone(),
+                row.raw_status.clone(),
+                row.count.to_string(),
+                row.fiat_volume.to_string(),
+                row.crypto_volume.to_string(),
+                row.tap_on_fees.to_string(),
+                base_currency.to_string(),
+                optional(row.fiat_volume_base),
+                optional(row.tap_on_fees_base),
+            ];
+            let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&line.join(","));
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+/// Aggregates callbacks by period, fiat, crypto, payment method and status.
+#[derive(Debug, Clone)]
+pub struct RevenueReporter {
+    period: ReportPeriod,
+    base: Option<(String, RateBook)>,
+}
+
+impl RevenueReporter {
+    pub fn new(period: ReportPeriod) -> Self {
+        Self { period, base: None }
+    }
+
+    /// Converts with the given snapshot, not historical rates at the time of each order.
+    pub fn with_base_currency(mut self, base_currency: impl Into<String>, rates: RateBook) -> Self {
+        self.base = Some((base_currency.into().to_uppercase(), rates));
+        self
+    }
+
+    pub fn report_orders(&self, orders: &[TrackedOrder]) -> RevenueReport {
+        let entries: Vec<ReportEntry> = orders.iter().map(ReportEntry::from).collect();
+
+        self.report(&entries)
+    }
+
+    /// Keeps the latest status of each transaction, so every order is counted once.
+    pub fn report_callbacks(&self, callbacks: Vec<ReportEntry>) -> RevenueReport {
+        let mut latest: HashMap<String, (ReportEntry, GateFiTransactionStatus)> = HashMap::new();
+
+        for entry in callbacks {
+            let status = entry.callback.parsed_status();
+            match latest.get_mut(&entry.callback.transaction_id) {
+                Some((current, current_status)) => {
+                    let received_at = entry.received_at.min(current.received_at);
+                    if status.progress() >= current_status.progress() {
+                        *current = entry;
+                        *current_status = status;
+                    }
+                    current.received_at = received_at;
+                }
+                None => {
+                    latest.insert(entry.callback.transaction_id.clone(), (entry, status));
+                }
+            }
+        }
+
+        let entries: Vec<ReportEntry> = latest.into_values().map(|(entry, _)| entry).collect();
+
+        self.report(&entries)
+    }
+
+    pub fn report(&self, entries: &[ReportEntry]) -> RevenueReport {
+        let mut groups: BTreeMap<GroupKey, RevenueReportRow> = BTreeMap::new();
+        let mut missing_rates = BTreeSet::new();
+        let mut skipped = Vec::new();
+
+        for entry in entries {
+            let callback = &entry.callback;
+            let status = callback.parsed_status();
+            let (Ok(fiat_volume), Ok(crypto_volume), Ok(tap_on_fee)) = (
+                amount_or_zero(&callback.fiat_amount, || callback.parsed_fiat_amount()),
+                amount_or_zero(&callback.crypto_amount, || callback.parsed_crypto_amount()),
+                callback.parsed_tap_on_fee(),
+            ) else {
+                skipped.push(callback.transaction_id.clone());
+                continue;
+            };
+
+            let fiat = callback.fiat_currency.to_uppercase();
+            let tap_on_fee = match tap_on_fee {
+                Some(fee) if fee.currency.is_empty() || fee.currency == fiat => fee.amount,
+                Some(fee) => match self.convert(fee.amount, &fee.currency, &fiat) {
+                    Some(amount) => amount,
+                    None => {
+                        missing_rates.insert(format!("{}/{}", fee.currency, fiat));
+                        Decimal::ZERO
+                    }
+                },
+                None => Decimal::ZERO,
+            };
+
+            let key = GroupKey {
+                period: self.period.key(entry.received_at),
+                fiat: fiat.clone(),
+                crypto: callback.crypto_currency.to_uppercase(),
+                payment_method: callback
+                    .payment_method
+                    .as_deref()
+                    .filter(|method| !method.is_empty())
+                    .unwrap_or(UNKNOWN_METHOD)
+                    .to_uppercase(),
+                progress: status.progress(),
+                raw_status: callback.normalized_status(),
+            };
+            let row = groups
+                .entry(key.clone())
+                .or_insert_with(|| RevenueReportRow {
+                    period: key.period,
+                    fiat: key.fiat,
+                    crypto: key.crypto,
+                    payment_method: key.payment_method,
+                    status,
+                    raw_status: key.raw_status,
+                    count: 0,
+                    fiat_volume: Decimal::ZERO,
+                    crypto_volume: Decimal::ZERO,
+                    tap_on_fees: Decimal::ZERO,
+                    fiat_volume_base: None,
+                    tap_on_fees_base: None,
+                });
+            row.count += 1;
+            row.fiat_volume += fiat_volume;
+            row.crypto_volume += crypto_volume;
+            row.tap_on_fees += tap_on_fee;
+        }
+
+        let mut rows: Vec<RevenueReportRow> = groups.into_values().collect();
+        if let Some((base_currency, _)) = &self.base {
+            for row in &mut rows {
+                row.fiat_volume_base = self.convert(row.fiat_volume, &row.fiat, base_currency);
+                row.tap_on_fees_base = self.convert(row.tap_on_fees, &row.fiat, base_currency);
+                if row.fiat_volume_base.is_none() || row.tap_on_fees_base.is_none() {
+                    missing_rates.insert(format!("{}/{}", row.fiat, base_currency));
+                }
+            }
+        }
+        skipped.sort();
+
+        RevenueReport {
+            period: self.period,
+            base_currency: self.base.as_ref().map(|(base, _)| base.clone()),
+            rows,
+            missing_rates: missing_rates.into_iter().collect(),
+            skipped,
+        }
+    }
+
+    fn convert(&self, amount: Decimal, from: &str, to: &str) -> Option<Decimal> {
+        if from.eq_ignore_ascii_case(to) {
+            return Some(amount);
+        }
+        let (_, rates) = self.base.as_ref()?;
+        // the shortest decimal form of the rate, so amounts are not rounded through f64
+        let rate = Decimal::from_str(&rates.rate(from, to).ok()?.to_string()).ok()?;
+
+        amount.checked_mul(rate).map(|amount| amount.round_dp(8))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct GroupKey {
+    period: String,
+    fiat: String,
+    crypto: String,
+    payment_method: String,
+    /// Sorts rows in flow order with unknown statuses first.
+    progress: Option<u8>,
+    /// Keeps statuses apart which are all `Unknown` to this client.
+    raw_status: String,
+}
+
+/// Callbacks before settlement may come without amounts.
+fn amount_or_zero(
+    value: &str,
+    parse: impl FnOnce() -> Result<Decimal, String>,
+) -> Result<Decimal, String> {
+    if value.trim().is_empty() {
+        return Ok(Decimal::ZERO);
+    }
+
+    parse()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Days since 1970-01-01 to (year, month, day), after Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhook::simulator::GateFiWebhookSimulator;
+
+    // 2024-05-17T00:00:00Z
+    const MAY_17: u64 = 1_715_904_000_000_000;
+
+    fn entry(id: &str, status: GateFiTransactionStatus, fiat_amount: &str) -> ReportEntry {
+        let mut callback = GateFiWebhookSimulator::sample_callback(status);
+        callback.transaction_id = id.to_string();
+        callback.fiat_currency = "EUR".to_string();
+        callback.fiat_amount = fiat_amount.to_string();
+        callback.crypto_amount = "0.001".to_string();
+        callback.payment_method = Some("BANKCARD".to_string());
+        callback.tap_on_fee_amount = Some("1.5".to_string());
+        callback.tap_on_fee_currency = Some("EUR".to_string());
+
+        ReportEntry {
+            received_at: MAY_17,
+            callback,
+        }
+    }
+
+    #[test]
+    fn aggregate_and_export() {
+        assert_eq!("2024-05-17", ReportPeriod::Day.key(MAY_17));
+        let rates = RateBook::new(
+            serde_json::from_str(r#"{"list":{"EUR":{"rates":{"USD":1.25}}}}"#).unwrap(),
+        );
+        let reporter = RevenueReporter::new(ReportPeriod::Month).with_base_currency("USD", rates);
+
+        let report = reporter.report_callbacks(vec![
+            entry("1", GateFiTransactionStatus::Created, "100"),
+            entry("1", GateFiTransactionStatus::Succeeded, "100"),
+            entry("2", GateFiTransactionStatus::Succeeded, "50"),
+        ]);
+
+        assert_eq!(1, report.rows.len());
+        let row = &report.rows[0];
+        assert_eq!("2024-05", row.period);
+        assert_eq!(2, row.count);
+        assert_eq!(Decimal::new(150, 0), row.fiat_volume);
+        assert_eq!(Decimal::new(3, 0), row.tap_on_fees);
+        assert_eq!(Some(Decimal::new(1875, 1)), row.fiat_volume_base);
+
+        let csv = report.to_csv();
+        assert!(csv.lines().nth(1).unwrap().starts_with("2024-05,EUR,"));
+
+        let mut refunded = entry("3", GateFiTransactionStatus::Succeeded, "10");
+        refunded.callback.status = "refunded".to_string();
+        let mut chargeback = entry("4", GateFiTransactionStatus::Succeeded, "10");
+        chargeback.callback.status = "chargeback".to_string();
+        let report = reporter.report(&[refunded, chargeback]);
+        let statuses: Vec<_> = report
+            .rows
+            .iter()
+            .map(|row| (row.status, row.raw_status.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (GateFiTransactionStatus::Unknown, "chargeback"),
+                (GateFiTransactionStatus::Unknown, "refunded"),
+            ],
+            statuses
+        );
+    }
+
+    #[test]
+    fn convert_with_decimal_rates() {
+        let rates = RateBook::new(
+            serde_json::from_str(r#"{"list":{"EUR":{"rates":{"USD":1.1}}}}"#).unwrap(),
+        );
+        let reporter = RevenueReporter::new(ReportPeriod::Month).with_base_currency("USD", rates);
+        let mut gbp = entry("2", GateFiTransactionStatus::Succeeded, "10");
+        gbp.callback.fiat_currency = "GBP".to_string();
+        gbp.callback.tap_on_fee_currency = Some("GBP".to_string());
+
+        let report = reporter.report(&[
+            entry("1", GateFiTransactionStatus::Succeeded, "123456789.12"),
+            gbp,
+        ]);
+
+        let eur = report.rows.iter().find(|row| row.fiat == "EUR").unwrap();
+        assert_eq!(Some(Decimal::new(135802468032, 3)), eur.fiat_volume_base);
+        assert_eq!(Some(Decimal::new(165, 2)), eur.tap_on_fees_base);
+        let gbp = report.rows.iter().find(|row| row.fiat == "GBP").unwrap();
+        assert_eq!(None, gbp.tap_on_fees_base);
+        assert_eq!(vec!["GBP/USD".to_string()], report.missing_rates);
+    }
+}
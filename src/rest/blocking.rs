@@ -0,0 +1,229 @@
+use crate::rest::config::{GateFiApiConfig, GateFiConfig};
+use crate::rest::endpoints::{
+    BuyAssetEndpoint, Endpoint, PaymentConfigEndpoint, PaymentMethodsEndpoint,
+    PlatformConfigEndpoint, QuotesEndpoint, RatesEndpoint, RawResponse,
+};
+use crate::rest::errors::Error;
+use crate::rest::models::{
+    GateFiBuyAssetResponse, GateFiPaymentConfigResponse, GateFiPaymentMethodsRequest,
+    GateFiPaymentMethodsResponse, GateFiPlatformConfigResponse, GateFiRatesResponse,
+    GetQuoteResponse,
+};
+use crate::rest::partner_fee::PartnerFeeConfig;
+use crate::rest::request_builder::GateFiRequestBuilder;
+use crate::rest::rest_client::GateFiBuyAssetParams;
+use crate::rest::schema::SchemaDriftObserver;
+use crate::rest::transport::PreparedRequest;
+use error_chain::bail;
+use std::sync::Arc;
+
+/// Synchronous counterpart of `GateFiRestClient`; must not be used from within an async runtime.
+#[derive(Clone)]
+pub struct GateFiBlockingClient {
+    builder: GateFiRequestBuilder,
+    inner_client: reqwest::blocking::Client,
+    no_redirect_client: reqwest::blocking::Client,
+}
+
+impl GateFiBlockingClient {
+    pub fn new(
+        partner_id: String,
+        secret_key: String,
+        access_key: String,
+        config: GateFiApiConfig,
+    ) -> Self {
+        Self {
+            builder: GateFiRequestBuilder::new(partner_id, secret_key, access_key, config),
+            inner_client: reqwest::blocking::Client::new(),
+            no_redirect_client: reqwest::blocking::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("must be valid client"),
+        }
+    }
+
+    pub fn from_config(config: &GateFiConfig) -> Result<Self, Error> {
+        config.validate()?;
+
+        Ok(Self::new(
+            config.partner_id.clone(),
+            config.secret_key.clone(),
+            config.access_key.clone(),
+            config.api_config(),
+        ))
+    }
+
+    /// Partner fees are sent with quotes and buy requests once their `params` are set.
+    pub fn with_partner_fees(mut self, partner_fees: PartnerFeeConfig) -> Self {
+        self.builder.set_partner_fees(partner_fees);
+        self
+    }
+
+    pub fn with_drift_observer(mut self, observer: Arc<dyn SchemaDriftObserver>) -> Self {
+        self.builder.set_drift_observer(observer);
+        self
+    }
+
+    pub fn partner_id(&self) -> &str {
+        self.builder.partner_id()
+    }
+
+    pub fn get_quote(
+        &self,
+        amount: impl Into<String>,
+        crypto_asset: impl Into<String>,
+        fiat_asset: impl Into<String>,
+        payment_method: impl Into<String>,
+        region: impl Into<String>,
+    ) -> Result<GetQuoteResponse, Error> {
+        let request = self.builder.quote_request(
+            amount.into(),
+            crypto_asset.into(),
+            fiat_asset.into(),
+            payment_method.into(),
+            region.into(),
+        );
+
+        self.execute::<QuotesEndpoint>(&request)
+    }
+
+    pub fn get_rates(&self) -> Result<GateFiRatesResponse, Error> {
+        self.execute::<RatesEndpoint>(&())
+    }
+
+    pub fn get_payment_methods(
+        &self,
+        currency_ico: impl Into<String>,
+        country_code: impl Into<String>,
+    ) -> Result<GateFiPaymentMethodsResponse, Error> {
+        let request = GateFiPaymentMethodsRequest {
+            currency_iso: currency_ico.into(),
+            country_code: country_code.into(),
+        };
+
+        self.execute::<PaymentMethodsEndpoint>(&request)
+    }
+
+    pub fn get_payment_config(&self) -> Result<GateFiPaymentConfigResponse, Error> {
+        self.execute::<PaymentConfigEndpoint>(&())
+    }
+
+    pub fn buy_asset(&self, params: GateFiBuyAssetParams) -> Result<GateFiBuyAssetResponse, Error> {
+        let request = self.builder.buy_request(params)?;
+        let mut response = self.execute::<BuyAssetEndpoint>(&request)?;
+        response.order_custom_id = request.order_custom_id;
+
+        Ok(response)
+    }
+
+    pub fn get_platform_config(&self) -> Result<GateFiPlatformConfigResponse, Error> {
+        self.execute::<PlatformConfigEndpoint>(&())
+    }
+
+    pub fn execute<E: Endpoint>(&self, request: &E::Request) -> Result<E::Response, Error> {
+        let request = self.prepare::<E>(request)?;
+        let client = if request.follow_redirects {
+            &self.inner_client
+        } else {
+            &self.no_redirect_client
+        };
+        let mut builder = client
+            .request(request.method.clone(), request.url.as_str())
+            .headers(request.headers.clone());
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        let response = builder.send()?;
+
+        let status = response.status().as_u16();
+        let url = response.url().to_string();
+        let headers = response.headers().clone();
+        let Ok(json) = response.text() else {
+            bail!("Failed to read response body. Url {}", request.url);
+        };
+
+        self.builder.handle_response::<E>(
+            RawResponse {
+                status,
+                url,
+                headers,
+                body: json,
+            },
+            &request,
+        )
+    }
+
+    fn prepare<E: Endpoint>(&self, request: &E::Request) -> Result<PreparedRequest, Error> {
+        self.builder.build::<E>(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::rest_client::GateFiRestClient;
+    use crate::rest::transport::GateFiTransport;
+    use async_trait::async_trait;
+    use reqwest::header::HeaderMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingTransport {
+        requests: Mutex<Vec<PreparedRequest>>,
+    }
+
+    #[async_trait]
+    impl GateFiTransport for RecordingTransport {
+        async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+            self.requests.lock().unwrap().push(request.clone());
+
+            Ok(RawResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: HeaderMap::new(),
+                body: r#"{"amountOut":"0.002"}"#.to_string(),
+            })
+        }
+    }
+
+    // a plain test, as the blocking client must not be dropped inside a runtime
+    #[test]
+    fn same_request_as_async_client() {
+        let transport = Arc::new(RecordingTransport::default());
+        let client = GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(transport.clone());
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(client.get_quote("100", "BTC", "USD", "BANKCARD", "US"))
+            .unwrap();
+        let sent = transport.requests.lock().unwrap().pop().unwrap();
+
+        let blocking = GateFiBlockingClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        );
+        let request = blocking.builder.quote_request(
+            "100".to_string(),
+            "BTC".to_string(),
+            "USD".to_string(),
+            "BANKCARD".to_string(),
+            "US".to_string(),
+        );
+        let prepared = blocking.prepare::<QuotesEndpoint>(&request).unwrap();
+
+        assert_eq!(sent.method, prepared.method);
+        assert_eq!(sent.url, prepared.url);
+        assert_eq!(sent.headers, prepared.headers);
+        assert_eq!(sent.body, prepared.body);
+        assert_eq!(sent.follow_redirects, prepared.follow_redirects);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::rest::endpoints::GateFiEndpoint;
+use crate::rest::errors::{Error, ErrorKind};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const CORRELATION_ID_HEADER: &str = "x-correlation-id";
+
+/// Options of a single call, see `GateFiRestClient::with_options`.
+#[derive(Debug, Clone, Default)]
+pub struct GateFiCallOptions {
+    pub deadline: Option<Instant>,
+    /// Counted from the start of each call; the earlier of this and `deadline` applies.
+    pub timeout: Option<Duration>,
+    pub cancellation_token: Option<CancellationToken>,
+    /// Sent as is and not covered by the request signature.
+    pub headers: HeaderMap,
+    pub idempotency_key: Option<String>,
+    pub correlation_id: Option<String>,
+}
+
+impl GateFiCallOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_idempotency_key(mut self, idempotency_key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(idempotency_key.into());
+        self
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    pub(crate) fn apply_headers(&self, headers: &mut HeaderMap) -> Result<(), Error> {
+        for (name, value) in &self.headers {
+            headers.insert(name.clone(), value.clone());
+        }
+        if let Some(idempotency_key) = &self.idempotency_key {
+            headers.insert(
+                IDEMPOTENCY_KEY_HEADER,
+                HeaderValue::from_str(idempotency_key)?,
+            );
+        }
+        if let Some(correlation_id) = &self.correlation_id {
+            headers.insert(
+                CORRELATION_ID_HEADER,
+                HeaderValue::from_str(correlation_id)?,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Drops `future` on deadline or cancellation, which aborts the in-flight request.
+    pub(crate) async fn run<T>(
+        &self,
+        endpoint: GateFiEndpoint,
+        future: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let cancelled = async {
+            match &self.cancellation_token {
+                Some(token) => token.cancelled().await,
+                None => std::future::pending().await,
+            }
+        };
+        let timeout = self.timeout.map(|timeout| Instant::now() + timeout);
+        let deadline = async {
+            match self.deadline.into_iter().chain(timeout).min() {
+                Some(deadline) => {
+                    tokio::time::sleep_until(tokio::time::Instant::from_std(deadline)).await
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            biased;
+            _ = cancelled => Err(ErrorKind::Cancelled(endpoint.path().to_string()).into()),
+            _ = deadline => Err(ErrorKind::Timeout(endpoint.path().to_string()).into()),
+            result = future => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::config::GateFiApiConfig;
+    use crate::rest::endpoints::RawResponse;
+    use crate::rest::rest_client::GateFiRestClient;
+    use crate::rest::transport::{GateFiTransport, PreparedRequest};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+
+    struct SlowTransport(Duration);
+
+    #[async_trait]
+    impl GateFiTransport for SlowTransport {
+        async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+            tokio::time::sleep(self.0).await;
+
+            Ok(RawResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: HeaderMap::new(),
+                body: "{}".to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn timeout_and_cancel() {
+        let client = GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(Arc::new(SlowTransport(Duration::from_secs(5))));
+
+        let options = GateFiCallOptions::new().with_timeout(Duration::from_millis(10));
+        let err = client.with_options(options).get_rates().await.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Timeout(_)));
+
+        let token = CancellationToken::new();
+        let options = GateFiCallOptions::new().with_cancellation_token(token.clone());
+        token.cancel();
+        let err = client.with_options(options).get_rates().await.unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Cancelled(_)));
+    }
+
+    #[tokio::test]
+    async fn timeout_per_call() {
+        let client = GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(Arc::new(SlowTransport(Duration::from_millis(10))));
+        let client =
+            client.with_options(GateFiCallOptions::new().with_timeout(Duration::from_millis(200)));
+
+        // options outliving their timeout still time each call on its own
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let result = client.get_rates().await;
+        assert!(!matches!(result, Err(Error(ErrorKind::Timeout(_), _))));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::rest::endpoints::RawResponse;
+use crate::rest::errors::Error;
+use crate::rest::transport::{GateFiTransport, PreparedRequest};
+use async_trait::async_trait;
+use error_chain::bail;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, SET_COOKIE};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use url::Url;
+
+pub const REDACTED: &str = "REDACTED";
+pub const DEFAULT_REDACTED_PARAMS: &[&str] = &["partnerAccountId"];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub body: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CassetteInteraction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Request headers are never recorded, so the access key and signatures stay out of cassettes.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Cassette {
+    pub interactions: Vec<CassetteInteraction>,
+}
+
+impl Cassette {
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let content = tokio::fs::read(path).await?;
+
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(self)?;
+        tokio::fs::write(path, json).await?;
+
+        Ok(())
+    }
+}
+
+pub struct RecordingTransport {
+    inner: Arc<dyn GateFiTransport>,
+    path: PathBuf,
+    redacted_params: Vec<String>,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingTransport {
+    /// Starts a new cassette at `path`, overwriting any previous recording.
+    pub fn new(inner: Arc<dyn GateFiTransport>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            inner,
+            path: path.into(),
+            redacted_params: default_redacted_params(),
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    pub fn with_redacted_params(mut self, redacted_params: Vec<String>) -> Self {
+        self.redacted_params = redacted_params;
+        self
+    }
+}
+
+#[async_trait]
+impl GateFiTransport for RecordingTransport {
+    async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+        let response = self.inner.send(request).await?;
+        let interaction = CassetteInteraction {
+            request: record_request(request, &self.redacted_params)?,
+            response: record_response(&response, &self.redacted_params)?,
+        };
+
+        let mut cassette = self.cassette.lock().await;
+        cassette.interactions.push(interaction);
+        cassette.save(&self.path).await?;
+
+        Ok(response)
+    }
+}
+
+/// Serves recorded interactions in order of recording and fails on unmatched requests.
+pub struct ReplayTransport {
+    redacted_params: Vec<String>,
+    interactions: Mutex<Vec<(CassetteInteraction, bool)>>,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            redacted_params: default_redacted_params(),
+            interactions: Mutex::new(
+                cassette
+                    .interactions
+                    .into_iter()
+                    .map(|interaction| (interaction, false))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::new(Cassette::load(path).await?))
+    }
+
+    pub fn with_redacted_params(mut self, redacted_params: Vec<String>) -> Self {
+        self.redacted_params = redacted_params;
+        self
+    }
+
+    pub async fn unused_interactions(&self) -> Vec<RecordedRequest> {
+        self.interactions
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, used)| !used)
+            .map(|(interaction, _)| interaction.request.clone())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl GateFiTransport for ReplayTransport {
+    async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+        let recorded = record_request(request, &self.redacted_params)?;
+        let mut interactions = self.interactions.lock().await;
+        let Some((interaction, used)) = interactions
+            .iter_mut()
+            .find(|(interaction, used)| !used && interaction.request == recorded)
+        else {
+            bail!("No recorded interaction for request {:?}", recorded);
+        };
+        *used = true;
+
+        replay_response(&interaction.response)
+    }
+}
+
+fn default_redacted_params() -> Vec<String> {
+    DEFAULT_REDACTED_PARAMS
+        .iter()
+        .map(|param| param.to_string())
+        .collect()
+}
+
+fn record_request(
+    request: &PreparedRequest,
+    redacted_params: &[String],
+) -> Result<RecordedRequest, Error> {
+    let url = Url::parse(&request.url)?;
+    let query = redact_query(&url, redacted_params);
+    let body = request
+        .body
+        .as_ref()
+        .map(|body| redact_body(body, redacted_params));
+
+    Ok(RecordedRequest {
+        method: request.method.to_string(),
+        path: url.path().to_string(),
+        query,
+        body,
+    })
+}
+
+fn redact_query(url: &Url, redacted_params: &[String]) -> Option<String> {
+    url.query().map(|_| {
+        url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(url.query_pairs().map(|(key, value)| {
+                if redacted_params.iter().any(|param| *param == key) {
+                    (key.into_owned(), REDACTED.to_string())
+                } else {
+                    (key.into_owned(), value.into_owned())
+                }
+            }))
+            .finish()
+    })
+}
+
+fn redact_body(body: &str, redacted_params: &[String]) -> String {
+    let Ok(Value::Object(mut json)) = serde_json::from_str::<Value>(body) else {
+        return body.to_string();
+    };
+    for param in redacted_params {
+        if let Some(value) = json.get_mut(param) {
+            *value = Value::String(REDACTED.to_string());
+        }
+    }
+
+    Value::Object(json).to_string()
+}
+
+fn record_response(
+    response: &RawResponse,
+    redacted_params: &[String],
+) -> Result<RecordedResponse, Error> {
+    // the response url repeats the request query
+    let mut url = Url::parse(&response.url)?;
+    let query = redact_query(&url, redacted_params);
+    url.set_query(query.as_deref());
+
+    Ok(RecordedResponse {
+        status: response.status,
+        url: url.to_string(),
+        headers: response
+            .headers
+            .iter()
+            .filter(|(name, _)| *name != SET_COOKIE)
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: response.body.clone(),
+    })
+}
+
+fn replay_response(response: &RecordedResponse) -> Result<RawResponse, Error> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &response.headers {
+        let Ok(name) = HeaderName::from_bytes(name.as_bytes()) else {
+            bail!("Invalid recorded header name {}", name);
+        };
+        headers.append(name, HeaderValue::from_str(value)?);
+    }
+
+    Ok(RawResponse {
+        status: response.status,
+        url: response.url.clone(),
+        headers,
+        body: response.body.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::config::GateFiApiConfig;
+    use crate::rest::rest_client::GateFiRestClient;
+
+    fn client(transport: ReplayTransport) -> GateFiRestClient {
+        GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(Arc::new(transport))
+    }
+
+    /// Answers every request with a quote and keeps the headers it was sent.
+    #[derive(Default)]
+    struct QuoteTransport {
+        headers: std::sync::Mutex<Vec<HeaderMap>>,
+    }
+
+    #[async_trait]
+    impl GateFiTransport for QuoteTransport {
+        async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+            self.headers.lock().unwrap().push(request.headers.clone());
+
+            Ok(RawResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: HeaderMap::new(),
+                body: r#"{"processingFee":"1","networkFee":"2","amountOut":"0.001"}"#.to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn record_redacted_interactions() {
+        let path =
+            std::env::temp_dir().join(format!("gatefi-cassette-{}.json", std::process::id()));
+        let inner = Arc::new(QuoteTransport::default());
+        let recording = Arc::new(RecordingTransport::new(inner.clone(), &path));
+        let client = GateFiRestClient::new(
+            "partner-7f3a".to_string(),
+            "secret-9c1d".to_string(),
+            "access-2b8e".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(recording.clone());
+
+        client
+            .get_quote("500", "BTC", "MXN", "BANKCARD", "MX")
+            .await
+            .unwrap();
+        let request = PreparedRequest {
+            method: reqwest::Method::POST,
+            url: "https://api-sandbox.gatefi.com/onramp/v1/orders".to_string(),
+            headers: HeaderMap::new(),
+            body: Some(r#"{"partnerAccountId":"partner-7f3a","amount":"500"}"#.to_string()),
+            follow_redirects: true,
+        };
+        recording.send(&request).await.unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let cassette: Cassette = serde_json::from_str(&written).unwrap();
+        assert_eq!(2, cassette.interactions.len());
+        assert!(cassette.interactions[0]
+            .request
+            .query
+            .as_deref()
+            .unwrap()
+            .contains("partnerAccountId=REDACTED"));
+        assert_eq!(
+            Some(r#"{"amount":"500","partnerAccountId":"REDACTED"}"#.to_string()),
+            cassette.interactions[1].request.body
+        );
+
+        let sent = inner.headers.lock().unwrap()[0].clone();
+        assert!(sent.contains_key("signature"));
+        for (name, value) in &sent {
+            assert!(!written.contains(name.as_str()), "{name} was recorded");
+            assert!(
+                !written.contains(value.to_str().unwrap()),
+                "{value:?} was recorded"
+            );
+        }
+        for secret in ["partner-7f3a", "secret-9c1d", "access-2b8e"] {
+            assert!(!written.contains(secret), "{secret} was recorded");
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_recorded_interactions() {
+        let cassette = Cassette {
+            interactions: vec![CassetteInteraction {
+                request: RecordedRequest {
+                    method: "GET".to_string(),
+                    path: "/onramp/v1/quotes".to_string(),
+                    query: Some(
+                        "amount=500&crypto=BTC&fiat=MXN&partnerAccountId=REDACTED&payment=BANKCARD&region=MX"
+                            .to_string(),
+                    ),
+                    body: None,
+                },
+                response: RecordedResponse {
+                    status: 200,
+                    url: "https://api-sandbox.gatefi.com/onramp/v1/quotes".to_string(),
+                    headers: vec![],
+                    body: r#"{"processingFee":"1","networkFee":"2","amountOut":"0.001"}"#
+                        .to_string(),
+                },
+            }],
+        };
+        let client = client(ReplayTransport::new(cassette));
+
+        let quote = client
+            .get_quote("500", "BTC", "MXN", "BANKCARD", "MX")
+            .await
+            .unwrap();
+        assert_eq!("0.001", quote.amount_out);
+
+        let unmatched = client
+            .get_quote("500", "BTC", "MXN", "BANKCARD", "MX")
+            .await;
+        assert!(unmatched.is_err());
+    }
+}
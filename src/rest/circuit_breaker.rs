@@ -0,0 +1,253 @@
+use crate::rest::endpoints::GateFiEndpoint;
+use crate::rest::errors::{Error, ErrorKind};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures which open the circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit rejects requests before letting a trial request through.
+    pub cool_down: Duration,
+    /// Successful trial requests needed to close a half-open circuit.
+    pub success_threshold: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+            success_threshold: 1,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    #[default]
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CircuitSnapshot {
+    pub endpoint: GateFiEndpoint,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub total_successes: u64,
+    pub total_failures: u64,
+    /// Requests failed fast with `CircuitOpen`.
+    pub rejected: u64,
+    /// Time left until a trial request is allowed, while open.
+    pub retry_in: Option<Duration>,
+}
+
+#[derive(Default)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    half_open_successes: u32,
+    opened_at: Option<Instant>,
+    trial_started_at: Option<Instant>,
+    total_successes: u64,
+    total_failures: u64,
+    rejected: u64,
+}
+
+impl Circuit {
+    fn open(&mut self, now: Instant) {
+        self.state = CircuitState::Open;
+        self.opened_at = Some(now);
+        self.trial_started_at = None;
+        self.half_open_successes = 0;
+    }
+
+    fn retry_in(&self, cool_down: Duration, now: Instant) -> Option<Duration> {
+        let opened_at = self.opened_at?;
+
+        Some(cool_down.saturating_sub(now.duration_since(opened_at)))
+    }
+}
+
+/// Tracks transport errors and 5xx responses per `GateFiEndpoint`; 4xx responses count as successes.
+pub struct GateFiCircuitBreaker {
+    config: CircuitBreakerConfig,
+    circuits: Mutex<HashMap<GateFiEndpoint, Circuit>>,
+}
+
+impl GateFiCircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &CircuitBreakerConfig {
+        &self.config
+    }
+
+    /// Fails with `CircuitOpen` when the request must not be sent.
+    pub fn acquire(&self, endpoint: GateFiEndpoint) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(endpoint).or_default();
+
+        let allowed = match circuit.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => {
+                let cooled_down = circuit
+                    .opened_at
+                    .map(|opened_at| now.duration_since(opened_at) >= self.config.cool_down)
+                    .unwrap_or(true);
+                if cooled_down {
+                    circuit.state = CircuitState::HalfOpen;
+                    circuit.half_open_successes = 0;
+                    circuit.trial_started_at = Some(now);
+                }
+                cooled_down
+            }
+            // A trial which never reported back is given up on after another cool-down.
+            CircuitState::HalfOpen => match circuit.trial_started_at {
+                Some(started_at) if now.duration_since(started_at) < self.config.cool_down => false,
+                _ => {
+                    circuit.trial_started_at = Some(now);
+                    true
+                }
+            },
+        };
+
+        if !allowed {
+            circuit.rejected += 1;
+            return Err(ErrorKind::CircuitOpen(endpoint.path().to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    pub fn record_success(&self, endpoint: GateFiEndpoint) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(endpoint).or_default();
+        circuit.total_successes += 1;
+        circuit.consecutive_failures = 0;
+
+        if circuit.state == CircuitState::HalfOpen {
+            circuit.half_open_successes += 1;
+            circuit.trial_started_at = None;
+            if circuit.half_open_successes >= self.config.success_threshold {
+                circuit.state = CircuitState::Closed;
+                circuit.opened_at = None;
+            }
+        }
+    }
+
+    pub fn record_failure(&self, endpoint: GateFiEndpoint) {
+        let now = Instant::now();
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(endpoint).or_default();
+        circuit.total_failures += 1;
+        circuit.consecutive_failures += 1;
+
+        match circuit.state {
+            CircuitState::HalfOpen => circuit.open(now),
+            CircuitState::Closed
+                if circuit.consecutive_failures >= self.config.failure_threshold =>
+            {
+                circuit.open(now)
+            }
+            _ => {}
+        }
+    }
+
+    pub fn reset(&self, endpoint: GateFiEndpoint) {
+        self.circuits.lock().unwrap().remove(&endpoint);
+    }
+
+    pub fn state(&self, endpoint: GateFiEndpoint) -> CircuitState {
+        self.circuits
+            .lock()
+            .unwrap()
+            .get(&endpoint)
+            .map(|circuit| circuit.state)
+            .unwrap_or_default()
+    }
+
+    /// True when no circuit is open, for health checks.
+    pub fn is_healthy(&self) -> bool {
+        self.circuits
+            .lock()
+            .unwrap()
+            .values()
+            .all(|circuit| circuit.state == CircuitState::Closed)
+    }
+
+    pub fn snapshot(&self) -> Vec<CircuitSnapshot> {
+        let now = Instant::now();
+        let circuits = self.circuits.lock().unwrap();
+
+        circuits
+            .iter()
+            .map(|(endpoint, circuit)| CircuitSnapshot {
+                endpoint: *endpoint,
+                state: circuit.state,
+                consecutive_failures: circuit.consecutive_failures,
+                total_successes: circuit.total_successes,
+                total_failures: circuit.total_failures,
+                rejected: circuit.rejected,
+                retry_in: match circuit.state {
+                    CircuitState::Open => circuit.retry_in(self.config.cool_down, now),
+                    _ => None,
+                },
+            })
+            .collect()
+    }
+}
+
+impl Default for GateFiCircuitBreaker {
+    fn default() -> Self {
+        Self::new(CircuitBreakerConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_half_open_and_close() {
+        let breaker = GateFiCircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            cool_down: Duration::from_millis(50),
+            success_threshold: 1,
+        });
+        let endpoint = GateFiEndpoint::Quotes;
+
+        breaker.record_failure(endpoint);
+        assert!(breaker.acquire(endpoint).is_ok());
+        breaker.record_failure(endpoint);
+        assert_eq!(CircuitState::Open, breaker.state(endpoint));
+
+        let err = breaker.acquire(endpoint).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::CircuitOpen(_)));
+        assert!(breaker.acquire(GateFiEndpoint::Rates).is_ok());
+        assert!(!breaker.is_healthy());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.acquire(endpoint).is_ok());
+        assert_eq!(CircuitState::HalfOpen, breaker.state(endpoint));
+        assert!(breaker.acquire(endpoint).is_err());
+
+        breaker.record_success(endpoint);
+        assert_eq!(CircuitState::Closed, breaker.state(endpoint));
+        assert!(breaker.is_healthy());
+        assert_eq!(
+            2,
+            breaker.snapshot().iter().map(|s| s.rejected).sum::<u64>()
+        );
+    }
+}
@@ -1,18 +1,300 @@
+use crate::rest::errors::Error;
+use error_chain::bail;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use url::Url;
+
+pub const SANDBOX_REST_API_HOST: &str = "https://api-sandbox.gatefi.com";
+pub const PROD_REST_API_HOST: &str = "https://api.gatefi.com";
+pub const SANDBOX_PAYMENT_PAGE_HOST: &str = "https://onramp-sandbox.gatefi.com";
+pub const PROD_PAYMENT_PAGE_HOST: &str = "https://onramp.gatefi.com";
+
 #[derive(Clone, Debug)]
 pub struct GateFiApiConfig {
     pub rest_api_host: String,
+    /// Buy redirects must point to one of these domains or their subdomains.
+    pub allowed_redirect_domains: Vec<String>,
+    /// Fail responses with fields unknown to or missing from the models, for contract tests.
+    pub strict_models: bool,
 }
 
 impl GateFiApiConfig {
     pub fn sandbox() -> Self {
         Self {
-            rest_api_host: "https://api-sandbox.gatefi.com".into(),
+            rest_api_host: SANDBOX_REST_API_HOST.into(),
+            allowed_redirect_domains: default_redirect_domains(),
+            strict_models: false,
         }
     }
 
     pub fn prod() -> Self {
         Self {
-            rest_api_host: "https://api.gatefi.com".into(),
+            rest_api_host: PROD_REST_API_HOST.into(),
+            allowed_redirect_domains: default_redirect_domains(),
+            strict_models: false,
         }
     }
 }
+
+impl GateFiApiConfig {
+    pub fn with_strict_models(mut self, strict_models: bool) -> Self {
+        self.strict_models = strict_models;
+        self
+    }
+}
+
+impl From<&GateFiConfig> for GateFiApiConfig {
+    fn from(config: &GateFiConfig) -> Self {
+        let mut api_config = match config.environment {
+            GateFiEnvironment::Prod => GateFiApiConfig::prod(),
+            GateFiEnvironment::Sandbox | GateFiEnvironment::Custom => GateFiApiConfig::sandbox(),
+        };
+        api_config.rest_api_host = config.rest_api_host();
+
+        api_config
+    }
+}
+
+fn default_redirect_domains() -> Vec<String> {
+    vec!["gatefi.com".into(), "unlimit.com".into()]
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GateFiEnvironment {
+    #[default]
+    Sandbox,
+    Prod,
+    /// Both hosts must be set explicitly.
+    Custom,
+}
+
+impl std::str::FromStr for GateFiEnvironment {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sandbox" => Ok(GateFiEnvironment::Sandbox),
+            "prod" | "production" => Ok(GateFiEnvironment::Prod),
+            "custom" => Ok(GateFiEnvironment::Custom),
+            _ => bail!("Unknown GateFi environment {}", s),
+        }
+    }
+}
+
+/// Everything needed to talk to GateFi, loadable from env vars, TOML or JSON files.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct GateFiConfig {
+    #[serde(default)]
+    pub environment: GateFiEnvironment,
+    /// Overrides the environment default.
+    pub rest_api_host: Option<String>,
+    /// Overrides the environment default.
+    pub payment_page_host: Option<String>,
+    #[serde(default)]
+    pub partner_id: String,
+    #[serde(default)]
+    pub access_key: String,
+    #[serde(default)]
+    pub secret_key: String,
+    /// Falls back to `secret_key` when not set.
+    pub webhook_secret: Option<String>,
+}
+
+impl fmt::Debug for GateFiConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GateFiConfig")
+            .field("environment", &self.environment)
+            .field("rest_api_host", &self.rest_api_host())
+            .field("payment_page_host", &self.payment_page_host())
+            .field("partner_id", &self.partner_id)
+            .field("access_key", &"***")
+            .field("secret_key", &"***")
+            .field(
+                "webhook_secret",
+                &self.webhook_secret.as_ref().map(|_| "***"),
+            )
+            .finish()
+    }
+}
+
+impl GateFiConfig {
+    pub fn sandbox(partner_id: String, access_key: String, secret_key: String) -> Self {
+        Self {
+            environment: GateFiEnvironment::Sandbox,
+            partner_id,
+            access_key,
+            secret_key,
+            ..Default::default()
+        }
+    }
+
+    pub fn prod(partner_id: String, access_key: String, secret_key: String) -> Self {
+        Self {
+            environment: GateFiEnvironment::Prod,
+            partner_id,
+            access_key,
+            secret_key,
+            ..Default::default()
+        }
+    }
+
+    /// Reads a `.toml` or `.json` file, then applies env overrides and validates the result.
+    pub fn load(path: Option<&Path>) -> Result<Self, Error> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_env() -> Result<Self, Error> {
+        Self::load(None)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml_str(&content),
+            Some("json") => Self::from_json_str(&content),
+            _ => bail!(
+                "Unsupported config file {}, expected .toml or .json",
+                path.display()
+            ),
+        }
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(content)?)
+    }
+
+    pub fn from_json_str(content: &str) -> Result<Self, Error> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    /// Overrides fields with `GATEFI_*` env vars which are set.
+    pub fn apply_env(&mut self) -> Result<(), Error> {
+        if let Some(value) = env_var("GATEFI_ENV") {
+            self.environment = value.parse()?;
+        }
+        if let Some(value) = env_var("GATEFI_REST_API_HOST") {
+            self.rest_api_host = Some(value);
+        }
+        if let Some(value) = env_var("GATEFI_PAYMENT_PAGE_HOST") {
+            self.payment_page_host = Some(value);
+        }
+        if let Some(value) = env_var("GATEFI_PARTNER_ID") {
+            self.partner_id = value;
+        }
+        if let Some(value) = env_var("GATEFI_ACCESS_KEY") {
+            self.access_key = value;
+        }
+        if let Some(value) = env_var("GATEFI_SECRET_KEY") {
+            self.secret_key = value;
+        }
+        if let Some(value) = env_var("GATEFI_WEBHOOK_SECRET") {
+            self.webhook_secret = Some(value);
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.partner_id.trim().is_empty() {
+            bail!("GateFi partner_id is not set");
+        }
+        if self.access_key.trim().is_empty() {
+            bail!("GateFi access_key is not set");
+        }
+        if self.secret_key.trim().is_empty() {
+            bail!("GateFi secret_key is not set");
+        }
+        if self.access_key == self.secret_key {
+            bail!("GateFi access_key and secret_key must differ");
+        }
+        if self.environment == GateFiEnvironment::Custom
+            && (self.rest_api_host.is_none() || self.payment_page_host.is_none())
+        {
+            bail!("Custom GateFi environment requires rest_api_host and payment_page_host");
+        }
+
+        validate_host("rest_api_host", &self.rest_api_host())?;
+        validate_host("payment_page_host", &self.payment_page_host())?;
+
+        Ok(())
+    }
+
+    pub fn rest_api_host(&self) -> String {
+        match (&self.rest_api_host, self.environment) {
+            (Some(host), _) => host.trim_end_matches('/').to_string(),
+            (None, GateFiEnvironment::Prod) => PROD_REST_API_HOST.to_string(),
+            (None, _) => SANDBOX_REST_API_HOST.to_string(),
+        }
+    }
+
+    pub fn payment_page_host(&self) -> String {
+        match (&self.payment_page_host, self.environment) {
+            (Some(host), _) => host.trim_end_matches('/').to_string(),
+            (None, GateFiEnvironment::Prod) => PROD_PAYMENT_PAGE_HOST.to_string(),
+            (None, _) => SANDBOX_PAYMENT_PAGE_HOST.to_string(),
+        }
+    }
+
+    pub fn webhook_secret(&self) -> &str {
+        self.webhook_secret.as_deref().unwrap_or(&self.secret_key)
+    }
+
+    pub fn api_config(&self) -> GateFiApiConfig {
+        GateFiApiConfig::from(self)
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn validate_host(name: &str, host: &str) -> Result<(), Error> {
+    let url = Url::parse(host)?;
+    let local = matches!(url.host_str(), Some("localhost") | Some("127.0.0.1"));
+
+    if url.scheme() != "https" && !(local && url.scheme() == "http") {
+        bail!("GateFi {} must use https: {}", name, host);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_toml_and_validate() {
+        let config = GateFiConfig::from_toml_str(
+            r#"
+            environment = "prod"
+            partner_id = "partner"
+            access_key = "access"
+            secret_key = "secret"
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.validate().is_ok());
+        assert_eq!(PROD_REST_API_HOST, config.rest_api_host());
+        assert_eq!(PROD_PAYMENT_PAGE_HOST, config.payment_page_host());
+        assert_eq!("secret", config.webhook_secret());
+
+        let swapped = GateFiConfig::sandbox("partner".into(), "same".into(), "same".into());
+        assert!(swapped.validate().is_err());
+
+        let mut custom = config.clone();
+        custom.environment = GateFiEnvironment::Custom;
+        assert!(custom.validate().is_err());
+    }
+}
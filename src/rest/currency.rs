@@ -1,68 +1,200 @@
+use crate::rest::errors::Error;
+use error_chain::bail;
+
 pub const CURRENCIES: &[Currency] = &[
     Currency {
         id: "BTC",
         symbol: "BTC",
         blockchain: "BTC",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "LTC",
         symbol: "LTC",
         blockchain: "LTC",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "BCH",
         symbol: "BCH",
         blockchain: "BCH",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "ADA",
         symbol: "ADA",
         blockchain: "ADA",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "DOGE",
         symbol: "DOGE",
         blockchain: "DOGE",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "XRP",
         symbol: "XRP",
         blockchain: "XRP",
+        memo: MemoSupport::DestinationTag,
+        memo_required: true,
     },
     Currency {
         id: "USDTE",
         symbol: "USDT",
         blockchain: "ERC20",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "USDTT",
         symbol: "USDT",
         blockchain: "TRC20",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "BNB",
         symbol: "BNB",
         blockchain: "BEP2",
+        memo: MemoSupport::Memo,
+        memo_required: true,
     },
     Currency {
         id: "EURS",
         symbol: "EURS",
         blockchain: "EURS",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "USDC",
         symbol: "USDC",
         blockchain: "ERC20",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
     Currency {
         id: "BNB-BSC",
         symbol: "BNB",
         blockchain: "BEP20",
+        memo: MemoSupport::None,
+        memo_required: false,
     },
 ];
 
+/// Destination tag or memo that exchange wallets use to tell deposits apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoSupport {
+    None,
+    /// XRP destination tag, an unsigned 32-bit integer.
+    DestinationTag,
+    /// BEP2 memo, up to 128 bytes of text.
+    Memo,
+}
+
+const MAX_MEMO_BYTES: usize = 128;
+
+impl MemoSupport {
+    pub fn is_supported(&self) -> bool {
+        *self != MemoSupport::None
+    }
+
+    pub fn validate(&self, memo: &str) -> Result<(), Error> {
+        match self {
+            MemoSupport::None => bail!("Memo is not supported"),
+            MemoSupport::DestinationTag => {
+                if memo.parse::<u32>().is_err() {
+                    bail!(
+                        "Invalid destination tag {}, must be a number up to {}",
+                        memo,
+                        u32::MAX
+                    );
+                }
+            }
+            MemoSupport::Memo => {
+                if memo.is_empty() || memo.len() > MAX_MEMO_BYTES {
+                    bail!("Invalid memo, must be 1 to {} bytes", MAX_MEMO_BYTES);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 pub struct Currency {
     pub id: &'static str,
     pub symbol: &'static str,
     pub blockchain: &'static str,
+    pub memo: MemoSupport,
+    /// Exchange wallets need the memo to credit a deposit to the right account.
+    pub memo_required: bool,
+}
+
+impl Currency {
+    /// Looks up by id, then by symbol when only one currency has it.
+    pub fn find(id_or_symbol: &str) -> Option<&'static Currency> {
+        if let Some(currency) = CURRENCIES
+            .iter()
+            .find(|currency| currency.id.eq_ignore_ascii_case(id_or_symbol))
+        {
+            return Some(currency);
+        }
+
+        let mut by_symbol = CURRENCIES
+            .iter()
+            .filter(|currency| currency.symbol.eq_ignore_ascii_case(id_or_symbol));
+        match (by_symbol.next(), by_symbol.next()) {
+            (Some(currency), None) => Some(currency),
+            _ => None,
+        }
+    }
+
+    /// Checks an optional wallet memo against the currency's chain. Deposits to an
+    /// `exchange_wallet` must have one where the chain's memo is required.
+    pub fn validate_memo(
+        currency: &str,
+        memo: Option<&str>,
+        exchange_wallet: bool,
+    ) -> Result<(), Error> {
+        let found = Currency::find(currency);
+        let Some(memo) = memo else {
+            if let Some(found) = found.filter(|found| exchange_wallet && found.memo_required) {
+                bail!("Exchange deposits of {} require a memo", found.id);
+            }
+            return Ok(());
+        };
+        match found {
+            Some(found) => match found.memo.validate(memo) {
+                Ok(()) => Ok(()),
+                Err(err) => bail!("{} for {}", err, found.id),
+            },
+            None => bail!("Unknown currency {}, can not validate memo", currency),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_memo() {
+        assert!(Currency::find("XRP").unwrap().memo.is_supported());
+        assert!(Currency::validate_memo("XRP", Some("123456"), true).is_ok());
+        assert!(Currency::validate_memo("XRP", Some("4294967296"), false).is_err());
+        assert!(Currency::validate_memo("BNB", Some("exchange-user-42"), true).is_ok());
+        assert!(Currency::validate_memo("BNB", Some(&"m".repeat(129)), false).is_err());
+        assert!(Currency::validate_memo("BTC", Some("1"), false).is_err());
+        assert!(Currency::validate_memo("BTC", None, true).is_ok());
+        assert!(Currency::validate_memo("XRP", None, false).is_ok());
+        assert!(Currency::validate_memo("XRP", None, true).is_err());
+        assert!(Currency::validate_memo("bnb", None, true).is_err());
+    }
 }
@@ -1,5 +1,32 @@
+use crate::rest::errors::Error;
+use crate::rest::models::{
+    GateFiBuyAssetRequest, GateFiBuyAssetResponse, GateFiPaymentConfigResponse,
+    GateFiPaymentMethodsRequest, GateFiPaymentMethodsResponse, GateFiPlatformConfigResponse,
+    GateFiRatesResponse, GetQuoteRequest, GetQuoteResponse,
+};
+use error_chain::bail;
 use http::Method;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::header::{HeaderMap, LOCATION, SET_COOKIE};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use url::Url;
 
+/// Escaped in path params: the url path set plus `/` and `%`, so a value stays one segment.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/')
+    .add(b'%');
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GateFiEndpoint {
     PlatformConfig,
     Quotes,
@@ -11,26 +38,322 @@ pub enum GateFiEndpoint {
 
 impl From<&GateFiEndpoint> for String {
     fn from(item: &GateFiEndpoint) -> Self {
-        String::from(match item {
-            GateFiEndpoint::PlatformConfig => "/onramp/v1/configuration",
-            GateFiEndpoint::Quotes => "/onramp/v1/quotes",
-            GateFiEndpoint::BuyAsset => "/onramp/v1/buy",
-            GateFiEndpoint::Rates => "/api/v1/rates",
-            GateFiEndpoint::PaymentConfig => "/api/v1/config",
-            GateFiEndpoint::PaymentMethods => "/api/v1/methods/currencies",
-        })
+        String::from(item.path())
     }
 }
 
 impl GateFiEndpoint {
+    pub fn path(&self) -> &'static str {
+        match &self {
+            GateFiEndpoint::PlatformConfig => PlatformConfigEndpoint::PATH,
+            GateFiEndpoint::Quotes => QuotesEndpoint::PATH,
+            GateFiEndpoint::BuyAsset => BuyAssetEndpoint::PATH,
+            GateFiEndpoint::Rates => RatesEndpoint::PATH,
+            GateFiEndpoint::PaymentConfig => PaymentConfigEndpoint::PATH,
+            GateFiEndpoint::PaymentMethods => PaymentMethodsEndpoint::PATH,
+        }
+    }
+
     pub fn get_http_method(&self) -> Method {
         match &self {
-            GateFiEndpoint::PlatformConfig => Method::GET,
-            GateFiEndpoint::Quotes => Method::GET,
-            GateFiEndpoint::BuyAsset => Method::GET,
-            GateFiEndpoint::Rates => Method::GET,
-            GateFiEndpoint::PaymentConfig => Method::GET,
-            GateFiEndpoint::PaymentMethods => Method::GET,
+            GateFiEndpoint::PlatformConfig => PlatformConfigEndpoint::METHOD,
+            GateFiEndpoint::Quotes => QuotesEndpoint::METHOD,
+            GateFiEndpoint::BuyAsset => BuyAssetEndpoint::METHOD,
+            GateFiEndpoint::Rates => RatesEndpoint::METHOD,
+            GateFiEndpoint::PaymentConfig => PaymentConfigEndpoint::METHOD,
+            GateFiEndpoint::PaymentMethods => PaymentMethodsEndpoint::METHOD,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestEncoding {
+    None,
+    Query,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct RawResponse {
+    pub status: u16,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+pub trait Endpoint {
+    type Request: Serialize;
+    /// `Default` describes the expected shape when a response fails to parse.
+    type Response: DeserializeOwned + Serialize + Default;
+
+    const KIND: GateFiEndpoint;
+    const METHOD: Method;
+    /// May contain `{name}` placeholders filled from `path_params`.
+    const PATH: &'static str;
+    const ENCODING: RequestEncoding;
+    /// When `false`, 3xx responses are passed to `decode` instead of being followed.
+    const FOLLOW_REDIRECTS: bool = true;
+
+    fn path_params(_request: &Self::Request) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    fn path(request: &Self::Request) -> String {
+        let mut path = Self::PATH.to_string();
+        for (name, value) in Self::path_params(request) {
+            let value = utf8_percent_encode(&value, PATH_SEGMENT).to_string();
+            path = path.replace(&format!("{{{name}}}"), &value);
+        }
+
+        path
+    }
+
+    fn decode(response: RawResponse) -> Result<Self::Response, Error> {
+        let body: Result<Self::Response, _> = serde_json::from_str(&response.body);
+        if let Err(err) = body {
+            bail!(
+                "Url {}. Failed to deserialize body {:?}: {}",
+                response.url,
+                err,
+                response.body
+            );
         }
+
+        Ok(body.unwrap())
+    }
+}
+
+pub struct PlatformConfigEndpoint;
+
+impl Endpoint for PlatformConfigEndpoint {
+    type Request = ();
+    type Response = GateFiPlatformConfigResponse;
+
+    const KIND: GateFiEndpoint = GateFiEndpoint::PlatformConfig;
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/onramp/v1/configuration";
+    const ENCODING: RequestEncoding = RequestEncoding::None;
+}
+
+pub struct QuotesEndpoint;
+
+impl Endpoint for QuotesEndpoint {
+    type Request = GetQuoteRequest;
+    type Response = GetQuoteResponse;
+
+    const KIND: GateFiEndpoint = GateFiEndpoint::Quotes;
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/onramp/v1/quotes";
+    const ENCODING: RequestEncoding = RequestEncoding::Query;
+}
+
+pub struct BuyAssetEndpoint;
+
+impl Endpoint for BuyAssetEndpoint {
+    type Request = GateFiBuyAssetRequest;
+    type Response = GateFiBuyAssetResponse;
+
+    const KIND: GateFiEndpoint = GateFiEndpoint::BuyAsset;
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/onramp/v1/buy";
+    const ENCODING: RequestEncoding = RequestEncoding::Query;
+    const FOLLOW_REDIRECTS: bool = false;
+
+    fn decode(response: RawResponse) -> Result<Self::Response, Error> {
+        let redirect_url = redirect_location(&response)?;
+        let metadata = response
+            .headers
+            .iter()
+            .filter(|(name, _)| *name != LOCATION && *name != SET_COOKIE)
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        Ok(GateFiBuyAssetResponse {
+            redirect_url: redirect_url.to_string(),
+            order_custom_id: String::new(),
+            status: response.status,
+            metadata,
+        })
+    }
+}
+
+/// The `Location` of a redirect response, resolved against the request url.
+pub(crate) fn redirect_location(response: &RawResponse) -> Result<Url, Error> {
+    let Some(location) = response
+        .headers
+        .get(LOCATION)
+        .and_then(|value| value.to_str().ok())
+    else {
+        bail!(
+            "Url {}. Expected redirect, received status {} without location",
+            response.url,
+            response.status
+        );
+    };
+
+    Ok(Url::parse(&response.url)?.join(location)?)
+}
+
+pub struct RatesEndpoint;
+
+impl Endpoint for RatesEndpoint {
+    type Request = ();
+    type Response = GateFiRatesResponse;
+
+    const KIND: GateFiEndpoint = GateFiEndpoint::Rates;
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/api/v1/rates";
+    const ENCODING: RequestEncoding = RequestEncoding::None;
+}
+
+pub struct PaymentConfigEndpoint;
+
+impl Endpoint for PaymentConfigEndpoint {
+    type Request = ();
+    type Response = GateFiPaymentConfigResponse;
+
+    const KIND: GateFiEndpoint = GateFiEndpoint::PaymentConfig;
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/api/v1/config";
+    const ENCODING: RequestEncoding = RequestEncoding::None;
+}
+
+pub struct PaymentMethodsEndpoint;
+
+impl Endpoint for PaymentMethodsEndpoint {
+    type Request = GateFiPaymentMethodsRequest;
+    type Response = GateFiPaymentMethodsResponse;
+
+    const KIND: GateFiEndpoint = GateFiEndpoint::PaymentMethods;
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "/api/v1/methods/currencies";
+    const ENCODING: RequestEncoding = RequestEncoding::Query;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::config::GateFiApiConfig;
+    use crate::rest::rest_client::GateFiRestClient;
+    use crate::rest::transport::{GateFiTransport, PreparedRequest};
+    use async_trait::async_trait;
+    use serde::Deserialize;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Serialize)]
+    struct OrderRequest {
+        id: String,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+    struct OrderResponse {
+        status: String,
+    }
+
+    struct OrderEndpoint;
+
+    impl Endpoint for OrderEndpoint {
+        type Request = OrderRequest;
+        type Response = OrderResponse;
+
+        const KIND: GateFiEndpoint = GateFiEndpoint::BuyAsset;
+        const METHOD: Method = Method::GET;
+        const PATH: &'static str = "/onramp/v1/orders/{id}";
+        const ENCODING: RequestEncoding = RequestEncoding::None;
+
+        fn path_params(request: &Self::Request) -> Vec<(&'static str, String)> {
+            vec![("id", request.id.clone())]
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingTransport {
+        urls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl GateFiTransport for RecordingTransport {
+        async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+            self.urls.lock().unwrap().push(request.url.clone());
+
+            Ok(RawResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: HeaderMap::new(),
+                body: r#"{"status":"created"}"#.to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn path_params_and_execute() {
+        let request = OrderRequest {
+            id: "a b/c+d%".to_string(),
+        };
+        assert_eq!(
+            "/onramp/v1/orders/a%20b%2Fc+d%25",
+            OrderEndpoint::path(&request)
+        );
+
+        let transport = Arc::new(RecordingTransport::default());
+        let client = GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(transport.clone());
+
+        let response = client.execute::<OrderEndpoint>(&request).await.unwrap();
+        assert_eq!("created", response.status);
+        assert_eq!(
+            vec!["https://api-sandbox.gatefi.com/onramp/v1/orders/a%20b%2Fc+d%25".to_string()],
+            *transport.urls.lock().unwrap()
+        );
+    }
+
+    struct QuoteTransport;
+
+    #[async_trait]
+    impl GateFiTransport for QuoteTransport {
+        async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+            Ok(RawResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: HeaderMap::new(),
+                body: r#"{"amountOut":"0.002","processingFee":"1","networkFee":"0.1","quoteId":"q-1"}"#
+                    .to_string(),
+            })
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct QuoteWithId {
+        #[serde(rename = "amountOut")]
+        amount_out: String,
+        #[serde(rename = "quoteId")]
+        quote_id: String,
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn untyped_reads_raw_body() {
+        let client = GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(Arc::new(QuoteTransport));
+
+        let quote: QuoteWithId = client
+            .get_signed(
+                GateFiEndpoint::Quotes,
+                Some("amount=100&crypto=BTC&fiat=USD&partnerAccountId=partner&payment=BANKCARD&region=US"),
+            )
+            .await
+            .unwrap();
+        assert_eq!("0.002", quote.amount_out);
+        assert_eq!("q-1", quote.quote_id);
     }
 }
@@ -10,6 +10,22 @@ pub struct CoinspaidContentError {
 error_chain! {
     errors {
        CoingeckoError(response: CoinspaidContentError)
+       InvalidSignature {
+           description("invalid signature")
+           display("Invalid signature")
+       }
+       CircuitOpen(endpoint: String) {
+           description("circuit open")
+           display("Circuit open for {}, request was not sent", endpoint)
+       }
+       Timeout(endpoint: String) {
+           description("deadline exceeded")
+           display("Deadline exceeded for {}", endpoint)
+       }
+       Cancelled(endpoint: String) {
+           description("cancelled")
+           display("Request to {} was cancelled", endpoint)
+       }
     }
     types {
         Error, ErrorKind, ResultExt, Result;
@@ -22,6 +38,8 @@ error_chain! {
         ParseFloatError(std::num::ParseFloatError);
         UrlParserError(url::ParseError);
         Json(serde_json::Error);
+        QueryString(serde_qs::Error);
+        Toml(toml::de::Error);
         TimestampError(std::time::SystemTimeError);
     }
 }
@@ -0,0 +1,221 @@
+use crate::rest::config::GateFiEnvironment;
+use crate::rest::currency::Currency;
+use crate::rest::errors::Error;
+use error_chain::bail;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl From<GateFiEnvironment> for Network {
+    /// Sandbox orders settle on test networks.
+    fn from(environment: GateFiEnvironment) -> Self {
+        match environment {
+            GateFiEnvironment::Prod => Network::Mainnet,
+            GateFiEnvironment::Sandbox | GateFiEnvironment::Custom => Network::Testnet,
+        }
+    }
+}
+
+impl Network {
+    /// Known EVM chain ids, as in `GateFiCryptoAsset::chain_id`.
+    pub fn from_chain_id(chain_id: &str) -> Option<Network> {
+        match chain_id.trim() {
+            "1" | "56" | "137" => Some(Network::Mainnet),
+            "5" | "97" | "17000" | "80002" | "11155111" => Some(Network::Testnet),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashFormat {
+    /// 64 hex characters, e.g. bitcoin-like chains.
+    Hex64,
+    /// `0x` followed by 64 hex characters, e.g. EVM chains.
+    PrefixedHex64,
+}
+
+impl HashFormat {
+    pub fn is_valid(&self, hash: &str) -> bool {
+        let hex = match self {
+            HashFormat::Hex64 => hash,
+            HashFormat::PrefixedHex64 => match hash.strip_prefix("0x") {
+                Some(hex) => hex,
+                None => return false,
+            },
+        };
+
+        hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChainExplorer {
+    /// Blockchains of `CURRENCIES` this explorer serves.
+    pub blockchains: &'static [&'static str],
+    pub hash_format: HashFormat,
+    /// Url templates where `{hash}` is replaced with the transaction hash.
+    pub mainnet_url: &'static str,
+    pub testnet_url: Option<&'static str>,
+}
+
+pub const CHAIN_EXPLORERS: &[ChainExplorer] = &[
+    ChainExplorer {
+        blockchains: &["BTC"],
+        hash_format: HashFormat::Hex64,
+        mainnet_url: "https://mempool.space/tx/{hash}",
+        testnet_url: Some("https://mempool.space/testnet/tx/{hash}"),
+    },
+    ChainExplorer {
+        blockchains: &["LTC"],
+        hash_format: HashFormat::Hex64,
+        mainnet_url: "https://litecoinspace.org/tx/{hash}",
+        testnet_url: Some("https://litecoinspace.org/testnet/tx/{hash}"),
+    },
+    ChainExplorer {
+        blockchains: &["BCH"],
+        hash_format: HashFormat::Hex64,
+        mainnet_url: "https://blockchair.com/bitcoin-cash/transaction/{hash}",
+        testnet_url: None,
+    },
+    ChainExplorer {
+        blockchains: &["ADA"],
+        hash_format: HashFormat::Hex64,
+        mainnet_url: "https://cardanoscan.io/transaction/{hash}",
+        testnet_url: Some("https://preprod.cardanoscan.io/transaction/{hash}"),
+    },
+    ChainExplorer {
+        blockchains: &["DOGE"],
+        hash_format: HashFormat::Hex64,
+        mainnet_url: "https://blockchair.com/dogecoin/transaction/{hash}",
+        testnet_url: Some("https://sochain.com/tx/DOGETEST/{hash}"),
+    },
+    ChainExplorer {
+        blockchains: &["XRP"],
+        hash_format: HashFormat::Hex64,
+        mainnet_url: "https://livenet.xrpl.org/transactions/{hash}",
+        testnet_url: Some("https://testnet.xrpl.org/transactions/{hash}"),
+    },
+    ChainExplorer {
+        blockchains: &["ERC20", "EURS"],
+        hash_format: HashFormat::PrefixedHex64,
+        mainnet_url: "https://etherscan.io/tx/{hash}",
+        testnet_url: Some("https://sepolia.etherscan.io/tx/{hash}"),
+    },
+    ChainExplorer {
+        blockchains: &["TRC20"],
+        hash_format: HashFormat::Hex64,
+        mainnet_url: "https://tronscan.org/#/transaction/{hash}",
+        testnet_url: Some("https://shasta.tronscan.org/#/transaction/{hash}"),
+    },
+    ChainExplorer {
+        blockchains: &["BEP2"],
+        hash_format: HashFormat::Hex64,
+        mainnet_url: "https://explorer.bnbchain.org/tx/{hash}",
+        testnet_url: Some("https://testnet-explorer.binance.org/tx/{hash}"),
+    },
+    ChainExplorer {
+        blockchains: &["BEP20"],
+        hash_format: HashFormat::PrefixedHex64,
+        mainnet_url: "https://bscscan.com/tx/{hash}",
+        testnet_url: Some("https://testnet.bscscan.com/tx/{hash}"),
+    },
+];
+
+impl ChainExplorer {
+    pub fn for_blockchain(blockchain: &str) -> Option<&'static ChainExplorer> {
+        CHAIN_EXPLORERS.iter().find(|explorer| {
+            explorer
+                .blockchains
+                .iter()
+                .any(|chain| chain.eq_ignore_ascii_case(blockchain))
+        })
+    }
+
+    pub fn for_currency(currency: &str) -> Option<&'static ChainExplorer> {
+        Self::for_blockchain(Currency::find(currency)?.blockchain)
+    }
+
+    pub fn tx_url(&self, hash: &str, network: Network) -> Result<String, Error> {
+        let hash = hash.trim();
+        if !self.hash_format.is_valid(hash) {
+            bail!("Invalid {} transaction hash {}", self.blockchains[0], hash);
+        }
+        let template = match network {
+            Network::Mainnet => self.mainnet_url,
+            Network::Testnet => match self.testnet_url {
+                Some(url) => url,
+                None => bail!("No testnet explorer for {}", self.blockchains[0]),
+            },
+        };
+
+        Ok(template.replace("{hash}", hash))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TransactionLink {
+    pub hash: String,
+    pub valid: bool,
+    /// `None` for invalid hashes and chains without an explorer for the network.
+    pub url: Option<String>,
+}
+
+pub fn transaction_links(
+    currency: &str,
+    hashes: &[String],
+    network: Network,
+) -> Vec<TransactionLink> {
+    let explorer = ChainExplorer::for_currency(currency);
+
+    hashes
+        .iter()
+        .map(|hash| {
+            let valid = explorer
+                .map(|explorer| explorer.hash_format.is_valid(hash.trim()))
+                .unwrap_or(false);
+            let url = explorer.and_then(|explorer| explorer.tx_url(hash, network).ok());
+
+            TransactionLink {
+                hash: hash.clone(),
+                valid,
+                url,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explorer_urls() {
+        let eth_hash = format!("0x{}", "ab".repeat(32));
+        let btc_hash = "cd".repeat(32);
+
+        let explorer = ChainExplorer::for_currency("USDC").unwrap();
+        assert_eq!(
+            format!("https://sepolia.etherscan.io/tx/{eth_hash}"),
+            explorer.tx_url(&eth_hash, Network::Testnet).unwrap()
+        );
+        assert!(explorer.tx_url(&btc_hash, Network::Mainnet).is_err());
+
+        let links = transaction_links(
+            "btc",
+            &[btc_hash.clone(), "not-a-hash".to_string()],
+            Network::Mainnet,
+        );
+        assert_eq!(
+            Some(format!("https://mempool.space/tx/{btc_hash}")),
+            links[0].url
+        );
+        assert!(!links[1].valid);
+        assert_eq!(Some(Network::Testnet), Network::from_chain_id("97"));
+    }
+}
@@ -1,7 +1,17 @@
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod call_options;
+pub mod cassette;
+pub mod circuit_breaker;
 pub mod config;
 pub mod currency;
 pub mod endpoints;
 pub mod errors;
+pub mod explorer;
 pub mod models;
+pub mod partner_fee;
+pub(crate) mod request_builder;
 pub mod request_signer;
 pub mod rest_client;
+pub mod schema;
+pub mod transport;
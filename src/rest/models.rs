@@ -1,6 +1,12 @@
+use crate::rest::explorer::{transaction_links, ChainExplorer, Network, TransactionLink};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::str::FromStr;
+
+/// Fields not described by a model; kept on deserialize and dropped on serialize.
+pub type GateFiExtraFields = HashMap<String, serde_json::Value>;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GetQuoteRequest {
@@ -13,6 +19,9 @@ pub struct GetQuoteRequest {
     pub payment: String,
     #[serde(rename = "region")]
     pub region: String,
+    /// Named as in `PartnerFeeParams`.
+    #[serde(flatten)]
+    pub partner_fee_params: BTreeMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,12 +32,14 @@ pub struct GateFiPaymentMethodsRequest {
     pub country_code: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GateFiPaymentMethodsResponse {
     #[serde(rename = "list")]
     pub list: Option<Vec<GateFiCurrencyPaymentMethod>>,
-    #[serde(rename = "total")]
+    #[serde(rename = "total", default)]
     pub total: i32,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -36,30 +47,37 @@ pub struct GateFiCurrencyPaymentMethod {
     #[serde(rename = "code")]
     pub code: String,
     #[serde(rename = "processingFee")]
-    pub processing_fee_percent: f64,
+    pub processing_fee_percent: Option<f64>,
     #[serde(rename = "processingFeeFix")]
-    pub processing_fee_fix: f64,
+    pub processing_fee_fix: Option<f64>,
     #[serde(rename = "processingFeeMin")]
-    pub processing_fee_min: f64,
+    pub processing_fee_min: Option<f64>,
     #[serde(rename = "openMode")]
-    pub open_mode: String,
-    #[serde(rename = "title")]
+    pub open_mode: Option<String>,
+    #[serde(rename = "title", default)]
     pub title: String,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GateFiPlatformConfigResponse {
+    #[serde(default)]
     pub version: String,
-    #[serde(rename = "updatedAt")]
+    #[serde(rename = "updatedAt", default)]
     pub updated_date: String,
-    #[serde(rename = "features")]
+    #[serde(rename = "features", default)]
     pub features: GateFiPlatformFeatures,
+    #[serde(default)]
     pub countries: Vec<GateFiPlatformCountry>,
+    #[serde(default)]
     pub payments: Vec<GateFiPlatformPayment>,
-    #[serde(rename = "fiat")]
+    #[serde(rename = "fiat", default)]
     pub fiat_assets: Vec<GateFiPlatformAsset>,
-    #[serde(rename = "crypto")]
+    #[serde(rename = "crypto", default)]
     pub crypto_assets: Vec<GateFiPlatformAsset>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -67,6 +85,8 @@ pub struct GateFiPlatformAsset {
     pub id: String,
     #[serde(rename = "paymentLimits")]
     pub payment_limits: Option<Vec<GateFiPlatformPaymentLimit>>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -74,42 +94,79 @@ pub struct GateFiPlatformPaymentLimit {
     pub id: String,
     pub min: String,
     pub max: String,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GateFiPlatformCountry {
     pub id: String,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GateFiPlatformPayment {
     pub id: String,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GateFiPlatformFeatures {
-    pub quotes: GateFiPlatformFeature,
-    pub buy: GateFiPlatformFeature,
+    pub quotes: Option<GateFiPlatformFeature>,
+    pub buy: Option<GateFiPlatformFeature>,
     #[serde(rename = "orderTracking")]
-    pub order_tracking: GateFiPlatformFeature,
+    pub order_tracking: Option<GateFiPlatformFeature>,
     #[serde(rename = "orderAnalytics")]
-    pub order_analytics: GateFiPlatformFeature,
+    pub order_analytics: Option<GateFiPlatformFeature>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
+}
+
+impl GateFiPlatformFeatures {
+    /// Looks up known and unknown features by their api name, e.g. `orderTracking`.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        let known = match name {
+            "quotes" => &self.quotes,
+            "buy" => &self.buy,
+            "orderTracking" => &self.order_tracking,
+            "orderAnalytics" => &self.order_analytics,
+            _ => {
+                return self
+                    .extra
+                    .get(name)
+                    .and_then(|feature| feature.get("enabled"))
+                    .and_then(|enabled| enabled.as_bool())
+                    .unwrap_or(false)
+            }
+        };
+
+        known
+            .as_ref()
+            .map(|feature| feature.enabled)
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GateFiPlatformFeature {
-    #[serde(rename = "enabled")]
+    #[serde(rename = "enabled", default)]
     pub enabled: bool,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GetQuoteResponse {
-    #[serde(rename = "processingFee")]
+    #[serde(rename = "processingFee", default)]
     pub processing_fee: String,
-    #[serde(rename = "networkFee")]
+    #[serde(rename = "networkFee", default)]
     pub network_fee: String,
     #[serde(rename = "amountOut")]
     pub amount_out: String,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -128,38 +185,60 @@ pub struct GateFiBuyAssetRequest {
     pub region: String,
     #[serde(rename = "walletAddress")]
     pub wallet_address: String,
+    /// The `walletMemo` name is not confirmed by GateFi yet.
+    #[serde(rename = "walletMemo", skip_serializing_if = "Option::is_none")]
+    pub wallet_memo: Option<String>,
+    /// Named as in `PartnerFeeParams`.
+    #[serde(flatten)]
+    pub partner_fee_params: BTreeMap<String, String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GateFiBuyAssetResponse {
     pub redirect_url: String,
+    #[serde(default)]
+    pub order_custom_id: String,
+    #[serde(default)]
+    pub status: u16,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GateFiRatesResponse {
     pub list: HashMap<String, GateFiRates>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GateFiRates {
+    #[serde(default)]
     pub rates: HashMap<String, f64>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct GateFiPaymentConfigResponse {
-    #[serde(rename = "availableNationalities")]
+    #[serde(rename = "availableNationalities", default)]
     pub available_nationalities: Vec<String>,
-    #[serde(rename = "availableCountries")]
+    #[serde(rename = "availableCountries", default)]
     pub available_countries: Vec<String>,
-    #[serde(rename = "fiat")]
+    #[serde(rename = "fiat", default)]
     pub fiat_assets: HashMap<String, GateFiFiatAsset>,
-    #[serde(rename = "crypto")]
+    #[serde(rename = "crypto", default)]
     pub crypto_assets: HashMap<String, GateFiCryptoAsset>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GateFiFiatAsset {
+    #[serde(default)]
     pub methods: HashMap<String, GateFiPaymentMethodInfo>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -167,29 +246,46 @@ pub struct GateFiPaymentMethodInfo {
     pub min: f64,
     pub max: f64,
     #[serde(rename = "processingFee")]
-    pub processing_fee_percent: f64,
-    pub precision: i32,
+    pub processing_fee_percent: Option<f64>,
+    pub precision: Option<i32>,
     #[serde(rename = "processingFeeFix")]
-    pub processing_fee_fix: f64,
+    pub processing_fee_fix: Option<f64>,
     #[serde(rename = "processingFeeMin")]
-    pub processing_fee_min: f64,
+    pub processing_fee_min: Option<f64>,
     #[serde(rename = "openMode")]
-    pub open_mode: String,
+    pub open_mode: Option<String>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GateFiCryptoAsset {
+    #[serde(default)]
     pub title: String,
     #[serde(rename = "type")]
     pub chain: String,
     pub symbol: String,
-    #[serde(rename = "chainId")]
+    #[serde(rename = "chainId", default)]
     pub chain_id: String,
     #[serde(rename = "networkFee")]
-    pub network_fee: f64,
-    pub precision: i32,
+    pub network_fee: Option<f64>,
+    pub precision: Option<i32>,
     pub min: f64,
     pub max: f64,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
+}
+
+impl GateFiCryptoAsset {
+    /// Known only for EVM chains.
+    pub fn network(&self) -> Option<Network> {
+        Network::from_chain_id(&self.chain_id)
+    }
+
+    pub fn explorer(&self) -> Option<&'static ChainExplorer> {
+        ChainExplorer::for_blockchain(&self.chain)
+            .or_else(|| ChainExplorer::for_currency(&self.symbol))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -202,6 +298,10 @@ pub struct GateFiCallbackData {
     pub custom_order_id: String,
     #[serde(rename = "destinationWallet")]
     pub destination_wallet: String,
+    /// XRP destination tag or BEP2 memo of the destination wallet. The
+    /// `destinationWalletMemo` name is not confirmed by GateFi yet.
+    #[serde(rename = "destinationWalletMemo")]
+    pub destination_wallet_memo: Option<String>,
     #[serde(rename = "fiatCurrency")]
     pub fiat_currency: String,
     #[serde(rename = "fiatAmount")]
@@ -219,14 +319,19 @@ pub struct GateFiCallbackData {
     pub fail_reason: Option<String>,
     #[serde(rename = "paymentMethod")]
     pub payment_method: Option<String>,
+    #[serde(flatten, skip_serializing)]
+    pub extra: GateFiExtraFields,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Not ordered: use `progress` to compare statuses, as `Unknown` has no place in the flow.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GateFiTransactionStatus {
     Init = 0,
     Created = 1,
     Succeeded = 2,
     Failed = 3,
+    #[serde(other)]
+    Unknown = 4,
 }
 
 impl fmt::Display for GateFiTransactionStatus {
@@ -236,6 +341,95 @@ impl fmt::Display for GateFiTransactionStatus {
             GateFiTransactionStatus::Created => write!(f, "created"),
             GateFiTransactionStatus::Succeeded => write!(f, "succeeded"),
             GateFiTransactionStatus::Failed => write!(f, "failed"),
+            GateFiTransactionStatus::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+impl FromStr for GateFiTransactionStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "init" => Ok(GateFiTransactionStatus::Init),
+            "created" => Ok(GateFiTransactionStatus::Created),
+            "succeeded" => Ok(GateFiTransactionStatus::Succeeded),
+            "failed" => Ok(GateFiTransactionStatus::Failed),
+            _ => Err(format!("Unknown transaction status {s}")),
+        }
+    }
+}
+
+impl GateFiTransactionStatus {
+    /// Like `from_str`, with statuses this client does not know yet mapped to `Unknown`.
+    pub fn parse_lenient(s: &str) -> Self {
+        s.parse().unwrap_or(GateFiTransactionStatus::Unknown)
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            GateFiTransactionStatus::Succeeded | GateFiTransactionStatus::Failed
+        )
+    }
+
+    /// Position in the transaction flow; `None` for statuses this client does not know.
+    pub fn progress(&self) -> Option<u8> {
+        match self {
+            GateFiTransactionStatus::Unknown => None,
+            status => Some(*status as u8),
         }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GateFiTapOnFee {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+impl GateFiCallbackData {
+    /// Statuses added by GateFi later are `Unknown` rather than an error.
+    pub fn parsed_status(&self) -> GateFiTransactionStatus {
+        GateFiTransactionStatus::parse_lenient(&self.status)
+    }
+
+    /// The status as sent, lowercased; tells apart statuses which are all `Unknown` here.
+    pub fn normalized_status(&self) -> String {
+        self.status.trim().to_lowercase()
+    }
+
+    pub fn parsed_fiat_amount(&self) -> Result<Decimal, String> {
+        parse_decimal("fiatAmount", &self.fiat_amount)
+    }
+
+    pub fn parsed_crypto_amount(&self) -> Result<Decimal, String> {
+        parse_decimal("cryptoAmount", &self.crypto_amount)
+    }
+
+    pub fn explorer_links(&self, network: Network) -> Vec<TransactionLink> {
+        transaction_links(
+            &self.crypto_currency,
+            self.transaction_hashes.as_deref().unwrap_or_default(),
+            network,
+        )
+    }
+
+    /// `None` when GateFi reports no fee, which it does with empty strings.
+    pub fn parsed_tap_on_fee(&self) -> Result<Option<GateFiTapOnFee>, String> {
+        let amount = self.tap_on_fee_amount.as_deref().unwrap_or("").trim();
+        let currency = self.tap_on_fee_currency.as_deref().unwrap_or("").trim();
+        if amount.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(GateFiTapOnFee {
+            amount: parse_decimal("tapOnFeeAmount", amount)?,
+            currency: currency.to_uppercase(),
+        }))
+    }
+}
+
+fn parse_decimal(name: &str, value: &str) -> Result<Decimal, String> {
+    Decimal::from_str(value.trim()).map_err(|err| format!("Invalid {name} {value}: {err}"))
+}
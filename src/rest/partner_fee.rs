@@ -0,0 +1,277 @@
+use crate::rest::errors::Error;
+use crate::rest::models::GetQuoteResponse;
+use error_chain::bail;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+/// Partner markup on top of GateFi fees, charged in the fiat currency of the order.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct PartnerFee {
+    /// Percent of the fiat amount, e.g. `1.5` for 1.5%.
+    pub percent: Option<Decimal>,
+    pub fixed: Option<Decimal>,
+}
+
+impl PartnerFee {
+    pub fn percent(percent: Decimal) -> Self {
+        Self {
+            percent: Some(percent),
+            fixed: None,
+        }
+    }
+
+    pub fn fixed(fixed: Decimal) -> Self {
+        Self {
+            percent: None,
+            fixed: Some(fixed),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.percent.is_none() && self.fixed.is_none()
+    }
+
+    pub fn amount(&self, fiat_amount: Decimal) -> Decimal {
+        let percent = self
+            .percent
+            .map(|percent| fiat_amount * percent / Decimal::ONE_HUNDRED)
+            .unwrap_or_default();
+
+        percent + self.fixed.unwrap_or_default()
+    }
+
+    /// Query or body parameters carrying the fee, named as in `names`.
+    pub fn params(&self, names: &PartnerFeeParams) -> BTreeMap<String, String> {
+        let mut params = BTreeMap::new();
+        if let Some(percent) = self.percent_param() {
+            params.insert(names.percent.clone(), percent);
+        }
+        if let Some(fixed) = self.fixed_param() {
+            params.insert(names.fixed.clone(), fixed);
+        }
+
+        params
+    }
+
+    pub(crate) fn percent_param(&self) -> Option<String> {
+        self.percent.map(|percent| percent.normalize().to_string())
+    }
+
+    pub(crate) fn fixed_param(&self) -> Option<String> {
+        self.fixed.map(|fixed| fixed.normalize().to_string())
+    }
+}
+
+/// Parameter names GateFi reads the partner fee from.
+///
+/// They are not part of the public api docs, so they have to be taken from the partner
+/// agreement, e.g. `tapOnFeePercent` and `tapOnFeeFixed`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PartnerFeeParams {
+    pub percent: String,
+    pub fixed: String,
+}
+
+impl PartnerFeeParams {
+    pub fn new(percent: impl Into<String>, fixed: impl Into<String>) -> Self {
+        Self {
+            percent: percent.into(),
+            fixed: fixed.into(),
+        }
+    }
+}
+
+/// Fee lookup by crypto asset, then payment method, then the default.
+///
+/// Without `params` the fees only show up in fee breakdowns and are not sent to GateFi.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PartnerFeeConfig {
+    #[serde(default)]
+    pub params: Option<PartnerFeeParams>,
+    pub default: Option<PartnerFee>,
+    #[serde(default)]
+    pub per_crypto: HashMap<String, PartnerFee>,
+    #[serde(default)]
+    pub per_method: HashMap<String, PartnerFee>,
+}
+
+impl PartnerFeeConfig {
+    pub fn new(default: Option<PartnerFee>) -> Self {
+        Self {
+            default,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_params(mut self, params: PartnerFeeParams) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    pub fn with_crypto_fee(mut self, crypto: impl Into<String>, fee: PartnerFee) -> Self {
+        self.per_crypto.insert(crypto.into(), fee);
+        self
+    }
+
+    pub fn with_method_fee(mut self, method: impl Into<String>, fee: PartnerFee) -> Self {
+        self.per_method.insert(method.into(), fee);
+        self
+    }
+
+    pub fn resolve(&self, crypto: &str, method: Option<&str>) -> Option<&PartnerFee> {
+        find_fee(&self.per_crypto, crypto)
+            .or_else(|| method.and_then(|method| find_fee(&self.per_method, method)))
+            .or(self.default.as_ref())
+    }
+}
+
+fn find_fee<'a>(fees: &'a HashMap<String, PartnerFee>, key: &str) -> Option<&'a PartnerFee> {
+    fees.iter()
+        .find(|(id, _)| id.eq_ignore_ascii_case(key))
+        .map(|(_, fee)| fee)
+}
+
+/// Fees of a quote in the fiat currency; `amount_out` is as quoted by GateFi.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FeeBreakdown {
+    pub fiat_currency: String,
+    pub crypto_currency: String,
+    pub fiat_amount: Decimal,
+    pub processing_fee: Decimal,
+    pub network_fee: Decimal,
+    pub partner_fee: Decimal,
+    pub total_fees: Decimal,
+    pub amount_out: Decimal,
+}
+
+impl FeeBreakdown {
+    pub fn from_quote(
+        fiat_currency: &str,
+        crypto_currency: &str,
+        fiat_amount: &str,
+        quote: &GetQuoteResponse,
+        partner_fee: Option<&PartnerFee>,
+    ) -> Result<Self, Error> {
+        let fiat_amount = parse_decimal("amount", fiat_amount)?;
+        let processing_fee = parse_decimal("processingFee", &quote.processing_fee)?;
+        let network_fee = parse_decimal("networkFee", &quote.network_fee)?;
+        let partner_fee = partner_fee
+            .map(|fee| fee.amount(fiat_amount))
+            .unwrap_or_default();
+
+        Ok(Self {
+            fiat_currency: fiat_currency.to_uppercase(),
+            crypto_currency: crypto_currency.to_uppercase(),
+            fiat_amount,
+            processing_fee,
+            network_fee,
+            partner_fee,
+            total_fees: processing_fee + network_fee + partner_fee,
+            amount_out: parse_decimal("amountOut", &quote.amount_out)?,
+        })
+    }
+}
+
+/// Empty values are read as zero.
+fn parse_decimal(name: &str, value: &str) -> Result<Decimal, Error> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(Decimal::ZERO);
+    }
+
+    match Decimal::from_str(value) {
+        Ok(value) => Ok(value),
+        Err(err) => bail!("Invalid {} {}: {}", name, value, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::config::GateFiApiConfig;
+    use crate::rest::endpoints::RawResponse;
+    use crate::rest::rest_client::GateFiRestClient;
+    use crate::rest::transport::{GateFiTransport, PreparedRequest};
+    use reqwest::header::HeaderMap;
+    use std::sync::Arc;
+
+    #[test]
+    fn resolve_and_break_down_fees() {
+        let config = PartnerFeeConfig::new(Some(PartnerFee::percent(Decimal::ONE)))
+            .with_crypto_fee("BTC", PartnerFee::fixed(Decimal::TWO))
+            .with_method_fee(
+                "SEPA",
+                PartnerFee {
+                    percent: Some(Decimal::new(5, 1)),
+                    fixed: Some(Decimal::ONE),
+                },
+            );
+
+        assert_eq!(
+            Some(&PartnerFee::fixed(Decimal::TWO)),
+            config.resolve("btc", Some("SEPA"))
+        );
+        let sepa = config.resolve("ETH", Some("sepa")).unwrap();
+        assert_eq!(Decimal::new(6, 0), sepa.amount(Decimal::new(1000, 0)));
+        assert_eq!(
+            Some("1"),
+            config
+                .resolve("ETH", None)
+                .unwrap()
+                .percent_param()
+                .as_deref()
+        );
+
+        let quote: GetQuoteResponse =
+            serde_json::from_str(r#"{"processingFee":"3.5","networkFee":"1","amountOut":"0.002"}"#)
+                .unwrap();
+        let breakdown = FeeBreakdown::from_quote("eur", "eth", "1000", &quote, Some(sepa)).unwrap();
+        assert_eq!(Decimal::new(105, 1), breakdown.total_fees);
+        assert_eq!("EUR", breakdown.fiat_currency);
+    }
+
+    struct QuoteTransport;
+
+    #[async_trait::async_trait]
+    impl GateFiTransport for QuoteTransport {
+        async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+            Ok(RawResponse {
+                status: 200,
+                url: request.url.clone(),
+                headers: HeaderMap::new(),
+                body: r#"{"processingFee":"3.5","networkFee":"1","amountOut":"0.002"}"#.to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn break_down_sent_fees_only() {
+        let fees = PartnerFeeConfig::new(Some(PartnerFee::fixed(Decimal::TWO)));
+        let client = GateFiRestClient::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+        .with_transport(Arc::new(QuoteTransport));
+        let breakdown = |client: GateFiRestClient| async move {
+            client
+                .get_fee_breakdown("100", "BTC", "EUR", "BANKCARD", "DE")
+                .await
+                .unwrap()
+        };
+
+        let unset = breakdown(client.clone().with_partner_fees(fees.clone())).await;
+        assert_eq!(Decimal::ZERO, unset.partner_fee);
+        assert_eq!(Decimal::new(45, 1), unset.total_fees);
+
+        let sent = breakdown(client.with_partner_fees(
+            fees.with_params(PartnerFeeParams::new("tapOnFeePercent", "tapOnFeeFixed")),
+        ))
+        .await;
+        assert_eq!(Decimal::TWO, sent.partner_fee);
+        assert_eq!(Decimal::new(65, 1), sent.total_fees);
+    }
+}
@@ -0,0 +1,570 @@
+use crate::rest::config::GateFiApiConfig;
+use crate::rest::currency::Currency;
+use crate::rest::endpoints::{redirect_location, Endpoint, RawResponse, RequestEncoding};
+use crate::rest::errors::Error;
+use crate::rest::models::{GateFiBuyAssetRequest, GetQuoteRequest};
+use crate::rest::partner_fee::{PartnerFee, PartnerFeeConfig, PartnerFeeParams};
+use crate::rest::request_signer::GateFiRequestSigner;
+use crate::rest::rest_client::GateFiBuyAssetParams;
+use crate::rest::schema::{detect_drift, detect_parse_drift, SchemaDriftObserver};
+use crate::rest::transport::PreparedRequest;
+use error_chain::bail;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use url::Url;
+
+/// Signing, url building and response handling shared by the async and blocking clients.
+#[derive(Clone)]
+pub(crate) struct GateFiRequestBuilder {
+    signer: GateFiRequestSigner,
+    access_key: String,
+    host: String,
+    partner_id: String,
+    allowed_redirect_domains: Vec<String>,
+    strict_models: bool,
+    drift_observer: Option<Arc<dyn SchemaDriftObserver>>,
+    partner_fees: Option<PartnerFeeConfig>,
+}
+
+impl GateFiRequestBuilder {
+    pub fn new(
+        partner_id: String,
+        secret_key: String,
+        access_key: String,
+        config: GateFiApiConfig,
+    ) -> Self {
+        Self {
+            signer: GateFiRequestSigner::new(secret_key),
+            access_key,
+            host: config.rest_api_host,
+            partner_id,
+            allowed_redirect_domains: config.allowed_redirect_domains,
+            strict_models: config.strict_models,
+            drift_observer: None,
+            partner_fees: None,
+        }
+    }
+
+    pub fn set_drift_observer(&mut self, observer: Arc<dyn SchemaDriftObserver>) {
+        self.drift_observer = Some(observer);
+    }
+
+    pub fn set_partner_fees(&mut self, partner_fees: PartnerFeeConfig) {
+        self.partner_fees = Some(partner_fees);
+    }
+
+    pub fn quote_request(
+        &self,
+        amount: String,
+        crypto: String,
+        fiat: String,
+        payment: String,
+        region: String,
+    ) -> GetQuoteRequest {
+        let fee = self.sent_partner_fee(&crypto, &payment);
+
+        GetQuoteRequest {
+            partner_fee_params: self.partner_fee_params(fee),
+            amount,
+            crypto,
+            fiat,
+            partner_id: self.partner_id.clone(),
+            payment,
+            region,
+        }
+    }
+
+    /// A fee set on `params` takes precedence over the configured partner fees, and needs
+    /// `PartnerFeeConfig::params` to be sent.
+    pub fn buy_request(
+        &self,
+        mut params: GateFiBuyAssetParams,
+    ) -> Result<GateFiBuyAssetRequest, Error> {
+        Currency::validate_memo(
+            &params.crypto,
+            params.wallet_memo.as_deref(),
+            params.exchange_wallet,
+        )?;
+        let fee = match params.partner_fee.take() {
+            Some(_) if self.partner_fee_names().is_none() => {
+                bail!(
+                    "Partner fee set for {} without partner fee params",
+                    params.order_custom_id
+                )
+            }
+            Some(fee) => Some(fee),
+            None => self
+                .sent_partner_fee(&params.crypto, &params.payment_method)
+                .cloned(),
+        };
+
+        let mut request = params.into_request(self.partner_id.clone());
+        request.partner_fee_params = self.partner_fee_params(fee.as_ref());
+
+        Ok(request)
+    }
+
+    pub fn partner_id(&self) -> &str {
+        &self.partner_id
+    }
+
+    /// The configured fee sent for `crypto` and `payment`; none until parameter names are set.
+    pub fn sent_partner_fee(&self, crypto: &str, payment: &str) -> Option<&PartnerFee> {
+        self.partner_fee_names()?;
+        self.partner_fees.as_ref()?.resolve(crypto, Some(payment))
+    }
+
+    fn partner_fee_names(&self) -> Option<&PartnerFeeParams> {
+        self.partner_fees.as_ref()?.params.as_ref()
+    }
+
+    /// Empty without a fee or without configured parameter names.
+    fn partner_fee_params(&self, fee: Option<&PartnerFee>) -> BTreeMap<String, String> {
+        match (fee, self.partner_fee_names()) {
+            (Some(fee), Some(names)) => fee.params(names),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    pub fn build<E: Endpoint>(&self, request: &E::Request) -> Result<PreparedRequest, Error> {
+        let path = E::path(request);
+        let url = match E::ENCODING {
+            RequestEncoding::Query => {
+                format!("{}{}?{}", self.host, path, serde_qs::to_string(request)?)
+            }
+            RequestEncoding::None | RequestEncoding::Json => format!("{}{}", self.host, path),
+        };
+        let body = match E::ENCODING {
+            RequestEncoding::Json => Some(serde_json::to_string(request)?),
+            RequestEncoding::None | RequestEncoding::Query => None,
+        };
+        let sign = self.signer.generate_path_sign(&E::METHOD, &path);
+        let mut headers = self.build_headers(Some(&sign));
+        if body.is_some() {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        }
+
+        Ok(PreparedRequest {
+            method: reqwest::Method::from_bytes(E::METHOD.as_str().as_bytes())
+                .expect("must be valid method"),
+            url,
+            headers,
+            body,
+            follow_redirects: E::FOLLOW_REDIRECTS,
+        })
+    }
+
+    pub fn validate_redirect(&self, redirect_url: &str) -> Result<(), Error> {
+        let url = Url::parse(redirect_url)?;
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        let allowed = self.allowed_redirect_domains.iter().any(|domain| {
+            let domain = domain.to_lowercase();
+            host == domain || host.ends_with(&format!(".{domain}"))
+        });
+
+        if url.scheme() != "https" || !allowed {
+            bail!("Unexpected redirect url {}", redirect_url);
+        }
+
+        Ok(())
+    }
+
+    fn build_headers(&self, sign: Option<&str>) -> HeaderMap {
+        let mut custom_headers = HeaderMap::new();
+
+        custom_headers.insert(
+            "access-control-allow-headers",
+            HeaderValue::from_str("Accept").unwrap(),
+        );
+
+        custom_headers.insert(
+            "X-merchantid",
+            HeaderValue::from_str(self.partner_id.as_str()).unwrap(),
+        );
+
+        custom_headers.insert(
+            HeaderName::from_static("api-key"),
+            HeaderValue::from_str(&self.access_key).unwrap(),
+        );
+
+        if let Some(sign) = sign {
+            custom_headers.insert(
+                HeaderName::from_static("signature"),
+                HeaderValue::from_str(sign).unwrap(),
+            );
+        }
+
+        custom_headers
+    }
+
+    pub fn handle_response<E: Endpoint>(
+        &self,
+        response: RawResponse,
+        request: &PreparedRequest,
+    ) -> Result<E::Response, Error> {
+        let request_url = &request.url;
+
+        match StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR) {
+            StatusCode::OK | StatusCode::CREATED => self.decode_checked::<E>(response, request),
+            s if s.is_redirection() && !request.follow_redirects => {
+                self.validate_redirect(redirect_location(&response)?.as_str())?;
+                E::decode(response)
+            }
+            StatusCode::INTERNAL_SERVER_ERROR => {
+                bail!("Internal Server Error {}", request_url,);
+            }
+            StatusCode::SERVICE_UNAVAILABLE => {
+                bail!("Service Unavailable {}", request_url,);
+            }
+            StatusCode::UNAUTHORIZED => {
+                bail!("Unauthorized {}", request_url);
+            }
+            StatusCode::BAD_REQUEST => {
+                bail!(format!(
+                    "Received bad request status. Url: {}. Request: {:?}. Response: {:?}",
+                    request_url, request.body, response.body
+                ));
+            }
+            s => {
+                bail!(format!(
+                    "Received response code: {s:?} error: {:?}",
+                    response.body
+                ));
+            }
+        }
+    }
+
+    fn decode_checked<E: Endpoint>(
+        &self,
+        response: RawResponse,
+        request: &PreparedRequest,
+    ) -> Result<E::Response, Error> {
+        if !self.strict_models && self.drift_observer.is_none() {
+            return E::decode(response);
+        }
+
+        // responses which are not json (e.g. redirects) have no schema to check
+        let Ok(raw) = serde_json::from_str::<Value>(&response.body) else {
+            return E::decode(response);
+        };
+        let model = match E::decode(response) {
+            Ok(model) => model,
+            Err(err) => {
+                if let Some(observer) = &self.drift_observer {
+                    let report = detect_parse_drift::<E::Response>(E::KIND, &raw, err.to_string())?;
+                    observer.on_drift(&report);
+                }
+                return Err(err);
+            }
+        };
+        let report = detect_drift(E::KIND, &raw, &model)?;
+
+        if let Some(observer) = &self.drift_observer {
+            if report.has_drift() {
+                observer.on_drift(&report);
+            }
+        }
+        if self.strict_models
+            && (!report.unknown_fields.is_empty() || !report.missing_fields.is_empty())
+        {
+            bail!(
+                "Url {}. Unknown fields in response: {:?}. Missing fields: {:?}",
+                request.url,
+                report.unknown_fields,
+                report.missing_fields
+            );
+        }
+
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::endpoints::{BuyAssetEndpoint, QuotesEndpoint};
+    use crate::rest::request_signer::GateFiSigner;
+    use crate::rest::schema::{SchemaDriftKind, SchemaDriftStats};
+    use reqwest::header::LOCATION;
+    use rust_decimal::Decimal;
+
+    fn builder() -> GateFiRequestBuilder {
+        GateFiRequestBuilder::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox(),
+        )
+    }
+
+    #[test]
+    fn build_signed_request() {
+        let builder = builder();
+        let request = builder.quote_request(
+            "100".to_string(),
+            "BTC".to_string(),
+            "USD".to_string(),
+            "BANKCARD".to_string(),
+            "US".to_string(),
+        );
+        let prepared = builder.build::<QuotesEndpoint>(&request).unwrap();
+
+        assert_eq!(reqwest::Method::GET, prepared.method);
+        assert_eq!(
+            "https://api-sandbox.gatefi.com/onramp/v1/quotes?amount=100&crypto=BTC&fiat=USD&partnerAccountId=partner&payment=BANKCARD&region=US",
+            prepared.url
+        );
+        assert_eq!(
+            GateFiSigner::generate_sign("secret", "GET/onramp/v1/quotes"),
+            prepared.headers["signature"]
+        );
+        assert_eq!("access", prepared.headers["api-key"]);
+        assert_eq!("partner", prepared.headers["X-merchantid"]);
+        assert!(prepared.body.is_none());
+        assert!(prepared.follow_redirects);
+    }
+
+    #[test]
+    fn validate_redirect_domains() {
+        let builder = builder();
+
+        assert!(builder
+            .validate_redirect("https://gatefi.com/order/1")
+            .is_ok());
+        assert!(builder
+            .validate_redirect("https://onramp-sandbox.gatefi.com/order/1")
+            .is_ok());
+        assert!(builder
+            .validate_redirect("https://gatefi.com.evil.example/order/1")
+            .is_err());
+        assert!(builder
+            .validate_redirect("https://evilgatefi.com/order/1")
+            .is_err());
+        assert!(builder
+            .validate_redirect("http://onramp.gatefi.com/order/1")
+            .is_err());
+    }
+
+    #[test]
+    fn buy_redirect_location() {
+        let builder = builder();
+        let request = builder
+            .build::<BuyAssetEndpoint>(
+                &builder
+                    .buy_request(GateFiBuyAssetParams {
+                        amount: "100".to_string(),
+                        crypto: "BTC".to_string(),
+                        fiat: "USD".to_string(),
+                        order_custom_id: "order-1".to_string(),
+                        payment_method: "BANKCARD".to_string(),
+                        redirect_url: String::new(),
+                        region: "US".to_string(),
+                        wallet_address: "mjEcj2LA3vj1nDi8ZD3QMCs9kNqVk7Dpee".to_string(),
+                        wallet_memo: None,
+                        exchange_wallet: false,
+                        partner_fee: None,
+                    })
+                    .unwrap(),
+            )
+            .unwrap();
+        let redirect = |location: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(location) = location {
+                headers.insert(LOCATION, HeaderValue::from_str(location).unwrap());
+            }
+            RawResponse {
+                status: 303,
+                url: request.url.clone(),
+                headers,
+                body: String::new(),
+            }
+        };
+
+        let response = builder
+            .handle_response::<BuyAssetEndpoint>(redirect(Some("/checkout/1")), &request)
+            .unwrap();
+        assert_eq!(
+            "https://api-sandbox.gatefi.com/checkout/1",
+            response.redirect_url
+        );
+        assert!(builder
+            .handle_response::<BuyAssetEndpoint>(
+                redirect(Some("https://evil.example/checkout/1")),
+                &request
+            )
+            .is_err());
+        assert!(builder
+            .handle_response::<BuyAssetEndpoint>(redirect(None), &request)
+            .is_err());
+    }
+
+    #[test]
+    fn strict_models() {
+        let response_with = |body: &str| RawResponse {
+            status: 200,
+            url: "https://api-sandbox.gatefi.com/onramp/v1/quotes".to_string(),
+            headers: HeaderMap::new(),
+            body: body.to_string(),
+        };
+        let response = || {
+            response_with(
+                r#"{"amountOut":"0.002","processingFee":"1","networkFee":"0.1","newField":1}"#,
+            )
+        };
+        let lenient = builder();
+        let request = lenient
+            .build::<QuotesEndpoint>(&lenient.quote_request(
+                "100".to_string(),
+                "BTC".to_string(),
+                "USD".to_string(),
+                "BANKCARD".to_string(),
+                "US".to_string(),
+            ))
+            .unwrap();
+        assert!(lenient
+            .handle_response::<QuotesEndpoint>(response(), &request)
+            .is_ok());
+
+        let strict = GateFiRequestBuilder::new(
+            "partner".to_string(),
+            "secret".to_string(),
+            "access".to_string(),
+            GateFiApiConfig::sandbox().with_strict_models(true),
+        );
+        let err = strict
+            .handle_response::<QuotesEndpoint>(response(), &request)
+            .unwrap_err();
+        assert!(err.to_string().contains("newField"));
+
+        // defaulted fields pass leniently but fail strict mode
+        let defaulted = || response_with(r#"{"amountOut":"0.002","networkFee":"0.1"}"#);
+        assert!(lenient
+            .handle_response::<QuotesEndpoint>(defaulted(), &request)
+            .is_ok());
+        let err = strict
+            .handle_response::<QuotesEndpoint>(defaulted(), &request)
+            .unwrap_err();
+        assert!(err.to_string().contains("processingFee"));
+
+        let no_amount = response_with(r#"{"processingFee":"1","networkFee":"0.1"}"#);
+        let err = strict
+            .handle_response::<QuotesEndpoint>(no_amount, &request)
+            .unwrap_err();
+        assert!(err.to_string().contains("amountOut"));
+    }
+
+    #[test]
+    fn observe_parse_drift() {
+        let stats = Arc::new(SchemaDriftStats::new());
+        let mut builder = builder();
+        builder.set_drift_observer(stats.clone());
+        let request = builder
+            .build::<QuotesEndpoint>(&builder.quote_request(
+                "100".to_string(),
+                "BTC".to_string(),
+                "USD".to_string(),
+                "BANKCARD".to_string(),
+                "US".to_string(),
+            ))
+            .unwrap();
+        let response = RawResponse {
+            status: 200,
+            url: request.url.clone(),
+            headers: HeaderMap::new(),
+            body: r#"{"amountOut":5,"processingFee":"1","networkFee":"0.1"}"#.to_string(),
+        };
+
+        assert!(builder
+            .handle_response::<QuotesEndpoint>(response, &request)
+            .is_err());
+        let kinds: Vec<_> = stats
+            .snapshot()
+            .into_iter()
+            .map(|(_, kind, path, count)| (kind, path, count))
+            .collect();
+        assert_eq!(
+            vec![
+                (SchemaDriftKind::ParseError, String::new(), 1),
+                (SchemaDriftKind::TypeMismatch, "amountOut".to_string(), 1),
+            ],
+            kinds
+        );
+    }
+
+    #[test]
+    fn buy_request_memo() {
+        let builder = builder();
+        let params = GateFiBuyAssetParams {
+            amount: "100".to_string(),
+            crypto: "XRP".to_string(),
+            fiat: "EUR".to_string(),
+            order_custom_id: "order-1".to_string(),
+            payment_method: "BANKCARD".to_string(),
+            redirect_url: String::new(),
+            region: "US".to_string(),
+            wallet_address: "rPEPPER7kfTD9w2To4CQk6UCfuHM9c6GDY".to_string(),
+            wallet_memo: None,
+            exchange_wallet: true,
+            partner_fee: None,
+        };
+        assert!(builder.buy_request(params.clone()).is_err());
+
+        let request = builder
+            .buy_request(GateFiBuyAssetParams {
+                wallet_memo: Some("12345".to_string()),
+                ..params
+            })
+            .unwrap();
+        let url = builder.build::<BuyAssetEndpoint>(&request).unwrap().url;
+        assert!(url.ends_with("&walletMemo=12345"));
+    }
+
+    #[test]
+    fn partner_fee_params() {
+        let quote = |builder: &GateFiRequestBuilder| {
+            builder
+                .build::<QuotesEndpoint>(&builder.quote_request(
+                    "100".to_string(),
+                    "BTC".to_string(),
+                    "USD".to_string(),
+                    "BANKCARD".to_string(),
+                    "US".to_string(),
+                ))
+                .unwrap()
+                .url
+        };
+        let fees = PartnerFeeConfig::new(Some(PartnerFee::percent(Decimal::ONE)));
+        let mut builder = builder();
+        builder.set_partner_fees(fees.clone());
+        assert!(!quote(&builder).contains("Fee"));
+
+        let params = GateFiBuyAssetParams {
+            amount: "100".to_string(),
+            crypto: "BTC".to_string(),
+            fiat: "USD".to_string(),
+            order_custom_id: "order-1".to_string(),
+            payment_method: "BANKCARD".to_string(),
+            redirect_url: String::new(),
+            region: "US".to_string(),
+            wallet_address: "mjEcj2LA3vj1nDi8ZD3QMCs9kNqVk7Dpee".to_string(),
+            wallet_memo: None,
+            exchange_wallet: false,
+            partner_fee: Some(PartnerFee::fixed(Decimal::TWO)),
+        };
+        assert!(builder.buy_request(params.clone()).is_err());
+
+        builder.set_partner_fees(
+            fees.with_params(PartnerFeeParams::new("tapOnFeePercent", "tapOnFeeFixed")),
+        );
+        assert!(quote(&builder).ends_with("&tapOnFeePercent=1"));
+        let request = builder.buy_request(params).unwrap();
+        assert_eq!(
+            BTreeMap::from([("tapOnFeeFixed".to_string(), "2".to_string())]),
+            request.partner_fee_params
+        );
+        assert_eq!("partner", builder.partner_id());
+    }
+}
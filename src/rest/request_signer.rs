@@ -1,4 +1,5 @@
 use crate::rest::endpoints::GateFiEndpoint;
+use http::Method;
 use ring::hmac;
 
 #[derive(Debug, Clone)]
@@ -15,6 +16,15 @@ impl GateFiSigner {
             .map(|byte| format!("{:02x}", byte))
             .collect()
     }
+
+    pub fn verify_sign(key: &str, data: &str, sign: &str) -> bool {
+        let Ok(sign) = hex::decode(sign.trim()) else {
+            return false;
+        };
+        let key = hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes());
+
+        hmac::verify(&key, data.as_bytes(), &sign).is_ok()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -28,14 +38,18 @@ impl GateFiRequestSigner {
     }
 
     pub fn generate_sign(&self, endpoint: &GateFiEndpoint) -> String {
-        let http_method = endpoint.get_http_method();
-        let data = format!("{}{}", http_method.as_str(), String::from(endpoint));
+        self.generate_path_sign(&endpoint.get_http_method(), endpoint.path())
+    }
+
+    pub fn generate_path_sign(&self, http_method: &Method, path: &str) -> String {
+        let data = format!("{}{}", http_method.as_str(), path);
 
         GateFiSigner::generate_sign(&self.secret_key, &data)
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow)]
 mod tests {
     use super::*;
 
@@ -60,4 +74,15 @@ mod tests {
         let source_sign = "a3c0624fdf21d89e4d35614874b3ac0ef1ed723606c39ab761c75d86e7be0047";
         assert_eq!(source_sign, sign);
     }
+
+    #[test]
+    fn verify_sign() {
+        let key = "JHDOFKyrhlonPfoXrXiMeFqKRhWYdXTv";
+        let data = "{\"status\":\"created\"}";
+        let sign = GateFiSigner::generate_sign(key, data);
+
+        assert!(GateFiSigner::verify_sign(key, data, &sign));
+        assert!(!GateFiSigner::verify_sign(key, "{}", &sign));
+        assert!(!GateFiSigner::verify_sign(key, data, "not-hex"));
+    }
 }
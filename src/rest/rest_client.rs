@@ -1,26 +1,32 @@
-use crate::rest::config::GateFiApiConfig;
-use crate::rest::endpoints::GateFiEndpoint;
-use crate::rest::errors::Error;
+use crate::rest::call_options::GateFiCallOptions;
+use crate::rest::circuit_breaker::GateFiCircuitBreaker;
+use crate::rest::config::{GateFiApiConfig, GateFiConfig};
+use crate::rest::endpoints::{
+    BuyAssetEndpoint, Endpoint, GateFiEndpoint, PaymentConfigEndpoint, PaymentMethodsEndpoint,
+    PlatformConfigEndpoint, QuotesEndpoint, RatesEndpoint, RawResponse,
+};
+use crate::rest::errors::{Error, ErrorKind};
 use crate::rest::models::{
     GateFiBuyAssetRequest, GateFiBuyAssetResponse, GateFiPaymentConfigResponse,
     GateFiPaymentMethodsRequest, GateFiPaymentMethodsResponse, GateFiPlatformConfigResponse,
-    GateFiRatesResponse, GetQuoteRequest, GetQuoteResponse,
+    GateFiRatesResponse, GetQuoteResponse,
 };
-use crate::rest::request_signer::GateFiRequestSigner;
-use error_chain::bail;
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use reqwest::Response;
-use reqwest::StatusCode;
+use crate::rest::partner_fee::{FeeBreakdown, PartnerFee, PartnerFeeConfig};
+use crate::rest::request_builder::GateFiRequestBuilder;
+use crate::rest::schema::SchemaDriftObserver;
+use crate::rest::transport::{GateFiTransport, PreparedRequest, ReqwestTransport};
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct GateFiRestClient {
-    signer: GateFiRequestSigner,
-    access_key: String,
-    host: String,
-    inner_client: reqwest::Client,
-    partner_id: String,
+    builder: GateFiRequestBuilder,
+    transport: Arc<dyn GateFiTransport>,
+    circuit_breaker: Option<Arc<GateFiCircuitBreaker>>,
+    call_options: GateFiCallOptions,
 }
 
 impl GateFiRestClient {
@@ -31,14 +37,74 @@ impl GateFiRestClient {
         config: GateFiApiConfig,
     ) -> Self {
         Self {
-            signer: GateFiRequestSigner::new(secret_key),
-            access_key,
-            host: config.rest_api_host,
-            inner_client: reqwest::Client::new(),
-            partner_id,
+            builder: GateFiRequestBuilder::new(partner_id, secret_key, access_key, config),
+            transport: Arc::new(ReqwestTransport::new()),
+            circuit_breaker: None,
+            call_options: GateFiCallOptions::default(),
         }
     }
 
+    pub fn from_config(config: &GateFiConfig) -> Result<Self, Error> {
+        Self::from_config_with_transport(config, Arc::new(ReqwestTransport::new()))
+    }
+
+    pub fn from_config_with_transport(
+        config: &GateFiConfig,
+        transport: Arc<dyn GateFiTransport>,
+    ) -> Result<Self, Error> {
+        config.validate()?;
+
+        Ok(Self {
+            builder: GateFiRequestBuilder::new(
+                config.partner_id.clone(),
+                config.secret_key.clone(),
+                config.access_key.clone(),
+                config.api_config(),
+            ),
+            transport,
+            circuit_breaker: None,
+            call_options: GateFiCallOptions::default(),
+        })
+    }
+
+    pub fn with_transport(mut self, transport: Arc<dyn GateFiTransport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// The breaker may be shared between clients to get one view of GateFi health.
+    pub fn with_circuit_breaker(mut self, circuit_breaker: Arc<GateFiCircuitBreaker>) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
+    pub fn circuit_breaker(&self) -> Option<&Arc<GateFiCircuitBreaker>> {
+        self.circuit_breaker.as_ref()
+    }
+
+    /// Returns a copy of the client whose calls use `options`, e.g.
+    /// `client.with_options(options).get_rates()`.
+    pub fn with_options(&self, call_options: GateFiCallOptions) -> Self {
+        let mut client = self.clone();
+        client.call_options = call_options;
+        client
+    }
+
+    /// Partner fees are sent with quotes and buy requests once their `params` are set.
+    pub fn with_partner_fees(mut self, partner_fees: PartnerFeeConfig) -> Self {
+        self.builder.set_partner_fees(partner_fees);
+        self
+    }
+
+    pub fn with_drift_observer(mut self, observer: Arc<dyn SchemaDriftObserver>) -> Self {
+        self.builder.set_drift_observer(observer);
+        self
+    }
+
+    pub fn partner_id(&self) -> &str {
+        self.builder.partner_id()
+    }
+
     pub async fn get_quote(
         &self,
         amount: impl Into<String>,
@@ -47,26 +113,50 @@ impl GateFiRestClient {
         payment_method: impl Into<String>,
         region: impl Into<String>,
     ) -> Result<GetQuoteResponse, Error> {
-        let request = GetQuoteRequest {
-            amount: amount.into(),
-            crypto: crypto_asset.into(),
-            fiat: fiat_asset.into(),
-            partner_id: self.partner_id.clone(),
-            payment: payment_method.into(),
-            region: region.into(),
-        };
-        let query_string = serde_qs::to_string(&request).unwrap(); // todo: handle err
-        let resp: GetQuoteResponse = self
-            .get_signed(GateFiEndpoint::Quotes, Some(&query_string))
-            .await?;
+        let request = self.builder.quote_request(
+            amount.into(),
+            crypto_asset.into(),
+            fiat_asset.into(),
+            payment_method.into(),
+            region.into(),
+        );
 
-        Ok(resp)
+        self.execute::<QuotesEndpoint>(&request).await
     }
 
-    pub async fn get_rates(&self) -> Result<GateFiRatesResponse, Error> {
-        let resp: GateFiRatesResponse = self.get_signed(GateFiEndpoint::Rates, None).await?;
+    /// Quotes with the configured partner fee and breaks down all fees. The partner fee is
+    /// only counted once it is sent, see `with_partner_fees`.
+    pub async fn get_fee_breakdown(
+        &self,
+        amount: impl Into<String>,
+        crypto_asset: impl Into<String>,
+        fiat_asset: impl Into<String>,
+        payment_method: impl Into<String>,
+        region: impl Into<String>,
+    ) -> Result<FeeBreakdown, Error> {
+        let request = self.builder.quote_request(
+            amount.into(),
+            crypto_asset.into(),
+            fiat_asset.into(),
+            payment_method.into(),
+            region.into(),
+        );
+        let quote = self.execute::<QuotesEndpoint>(&request).await?;
+        let partner_fee = self
+            .builder
+            .sent_partner_fee(&request.crypto, &request.payment);
+
+        FeeBreakdown::from_quote(
+            &request.fiat,
+            &request.crypto,
+            &request.amount,
+            &quote,
+            partner_fee,
+        )
+    }
 
-        Ok(resp)
+    pub async fn get_rates(&self) -> Result<GateFiRatesResponse, Error> {
+        self.execute::<RatesEndpoint>(&()).await
     }
 
     pub async fn get_payment_methods(
@@ -78,126 +168,142 @@ impl GateFiRestClient {
             currency_iso: currency_ico.into(),
             country_code: country_code.into(),
         };
-        let query = serde_qs::to_string(&request).unwrap();
-        let resp = self
-            .get_signed(GateFiEndpoint::PaymentMethods, Some(&query))
-            .await?;
 
-        Ok(resp)
+        self.execute::<PaymentMethodsEndpoint>(&request).await
     }
 
     pub async fn get_payment_config(&self) -> Result<GateFiPaymentConfigResponse, Error> {
-        let resp: GateFiPaymentConfigResponse =
-            self.get_signed(GateFiEndpoint::PaymentConfig, None).await?;
-
-        Ok(resp)
+        self.execute::<PaymentConfigEndpoint>(&()).await
     }
 
     pub async fn buy_asset(
         &self,
         params: GateFiBuyAssetParams,
     ) -> Result<GateFiBuyAssetResponse, Error> {
-        let request = GateFiBuyAssetRequest {
-            amount: params.amount,
-            crypto: params.crypto,
-            fiat: params.fiat,
-            order_custom_id: params.order_custom_id,
-            partner_account_id: self.partner_id.clone(),
-            payment_method: params.payment_method.to_string(),
-            redirect_url: params.redirect_url,
-            region: params.region,
-            wallet_address: params.wallet_address,
-        };
-        let query_params = serde_qs::to_string(&request).unwrap(); // todo: handle err
-        let endpoint = GateFiEndpoint::BuyAsset;
-        let url = format!("{}{}?{}", self.host, String::from(&endpoint), query_params);
-        let sign = self.signer.generate_sign(&endpoint);
-        let client = &self.inner_client;
-        let headers = self.build_headers(Some(&sign));
-        let response = client.get(url.as_str()).headers(headers).send().await?;
-
-        if response.status() != StatusCode::OK {
-            return self.handler(response, None, &url).await;
-        }
+        let request = self.builder.buy_request(params)?;
+        let mut response = self.execute::<BuyAssetEndpoint>(&request).await?;
+        response.order_custom_id = request.order_custom_id;
 
-        Ok(GateFiBuyAssetResponse {
-            redirect_url: response.url().to_string(),
-        })
+        Ok(response)
     }
 
     pub async fn get_platform_config(&self) -> Result<GateFiPlatformConfigResponse, Error> {
-        let resp: GateFiPlatformConfigResponse = self
-            .get_signed(GateFiEndpoint::PlatformConfig, None)
-            .await?;
+        self.execute::<PlatformConfigEndpoint>(&()).await
+    }
 
-        Ok(resp)
+    pub async fn execute<E: Endpoint>(&self, request: &E::Request) -> Result<E::Response, Error> {
+        self.execute_with_options::<E>(request, &self.call_options)
+            .await
     }
 
-    pub async fn post_signed<T: DeserializeOwned>(
+    pub async fn execute_with_options<E: Endpoint>(
         &self,
-        endpoint: GateFiEndpoint,
-        request_json: String,
-    ) -> Result<T, Error> {
-        let url: String = format!("{}{}", self.host, String::from(&endpoint));
-        let sign = self.signer.generate_sign(&endpoint);
-
-        let headers = self.build_headers(Some(&sign));
-        let client = &self.inner_client;
-        let response = client
-            .post(&url)
-            .body(request_json.clone())
-            .headers(headers)
-            .send()
-            .await?;
-
-        self.handler(response, Some(request_json), &url).await
+        request: &E::Request,
+        options: &GateFiCallOptions,
+    ) -> Result<E::Response, Error> {
+        let (response, request) = self.send::<E>(request, options).await?;
+
+        self.builder.handle_response::<E>(response, &request)
+    }
+
+    async fn send<E: Endpoint>(
+        &self,
+        request: &E::Request,
+        options: &GateFiCallOptions,
+    ) -> Result<(RawResponse, PreparedRequest), Error> {
+        let mut request = self.builder.build::<E>(request)?;
+        options.apply_headers(&mut request.headers)?;
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            circuit_breaker.acquire(E::KIND)?;
+        }
+        let response = options.run(E::KIND, self.transport.send(&request)).await;
+        if let Some(circuit_breaker) = &self.circuit_breaker {
+            match &response {
+                Ok(response) if response.status < 500 => circuit_breaker.record_success(E::KIND),
+                Err(Error(ErrorKind::Cancelled(_), _)) => {}
+                _ => circuit_breaker.record_failure(E::KIND),
+            }
+        }
+
+        Ok((response?, request))
     }
 
+    #[deprecated(note = "use `execute` with a typed `Endpoint`")]
     pub async fn get_signed<T: DeserializeOwned>(
         &self,
         endpoint: GateFiEndpoint,
         query_params: Option<&str>,
     ) -> Result<T, Error> {
-        let url: String = if let Some(query_params) = query_params {
-            format!("{}{}?{}", self.host, String::from(&endpoint), query_params)
-        } else {
-            format!("{}{}", self.host, String::from(&endpoint))
-        };
-        let sign = self.signer.generate_sign(&endpoint);
-
-        let client = &self.inner_client;
-        let headers = self.build_headers(Some(&sign));
-        let response = client.get(url.as_str()).headers(headers).send().await?;
-
-        self.handler(response, None, &url).await
+        let query = query_params.unwrap_or_default();
+        match endpoint {
+            GateFiEndpoint::PlatformConfig => {
+                self.execute_untyped::<PlatformConfigEndpoint, T>(&()).await
+            }
+            GateFiEndpoint::Quotes => {
+                self.execute_untyped::<QuotesEndpoint, T>(&serde_qs::from_str(query)?)
+                    .await
+            }
+            GateFiEndpoint::BuyAsset => {
+                self.execute_untyped::<BuyAssetEndpoint, T>(&serde_qs::from_str(query)?)
+                    .await
+            }
+            GateFiEndpoint::Rates => self.execute_untyped::<RatesEndpoint, T>(&()).await,
+            GateFiEndpoint::PaymentConfig => {
+                self.execute_untyped::<PaymentConfigEndpoint, T>(&()).await
+            }
+            GateFiEndpoint::PaymentMethods => {
+                self.execute_untyped::<PaymentMethodsEndpoint, T>(&serde_qs::from_str(query)?)
+                    .await
+            }
+        }
     }
 
-    fn build_headers(&self, sign: Option<&str>) -> HeaderMap {
-        let mut custom_headers = HeaderMap::new();
-
-        custom_headers.insert(
-            "access-control-allow-headers",
-            HeaderValue::from_str("Accept").unwrap(),
-        );
-
-        custom_headers.insert(
-            "X-merchantid",
-            HeaderValue::from_str(self.partner_id.as_str()).unwrap(),
-        );
-
-        custom_headers.insert(
-            HeaderName::from_static("api-key"),
-            HeaderValue::from_str(&self.access_key).unwrap(),
-        );
-
-        if let Some(sign) = sign {
-            custom_headers.insert(
-                HeaderName::from_static("signature"),
-                HeaderValue::from_str(sign).unwrap(),
-            );
+    /// The request is sent with the method of the endpoint.
+    #[deprecated(note = "use `execute` with a typed `Endpoint`")]
+    pub async fn post_signed<T: DeserializeOwned>(
+        &self,
+        endpoint: GateFiEndpoint,
+        request_json: String,
+    ) -> Result<T, Error> {
+        match endpoint {
+            GateFiEndpoint::PlatformConfig => {
+                self.execute_untyped::<PlatformConfigEndpoint, T>(&()).await
+            }
+            GateFiEndpoint::Quotes => {
+                self.execute_untyped::<QuotesEndpoint, T>(&serde_json::from_str(&request_json)?)
+                    .await
+            }
+            GateFiEndpoint::BuyAsset => {
+                self.execute_untyped::<BuyAssetEndpoint, T>(&serde_json::from_str(&request_json)?)
+                    .await
+            }
+            GateFiEndpoint::Rates => self.execute_untyped::<RatesEndpoint, T>(&()).await,
+            GateFiEndpoint::PaymentConfig => {
+                self.execute_untyped::<PaymentConfigEndpoint, T>(&()).await
+            }
+            GateFiEndpoint::PaymentMethods => {
+                self.execute_untyped::<PaymentMethodsEndpoint, T>(&serde_json::from_str(
+                    &request_json,
+                )?)
+                .await
+            }
         }
+    }
 
-        custom_headers
+    async fn execute_untyped<E: Endpoint, T: DeserializeOwned>(
+        &self,
+        request: &E::Request,
+    ) -> Result<T, Error> {
+        let (response, request) = self.send::<E>(request, &self.call_options).await?;
+        let body = response.body.clone();
+        let model = self.builder.handle_response::<E>(response, &request)?;
+
+        // fields the model does not know about still reach T; only redirects without a
+        // json body go through the model
+        match serde_json::from_str::<Value>(&body) {
+            Ok(raw) => Ok(serde_json::from_value(raw)?),
+            Err(_) => Ok(serde_json::from_value(serde_json::to_value(model)?)?),
+        }
     }
 
     pub fn build_query(&self, parameters: HashMap<String, String>) -> String {
@@ -209,70 +315,9 @@ impl GateFiRestClient {
         request.pop();
         request
     }
-
-    async fn handler<T: DeserializeOwned>(
-        &self,
-        response: Response,
-        request_json: Option<String>,
-        request_url: &str,
-    ) -> Result<T, Error> {
-        match response.status() {
-            StatusCode::OK => {
-                let json: Result<String, _> = response.text().await;
-                let Ok(json) = json else {
-                    bail!("Failed to read response body. Url {}", request_url);
-                };
-
-                let body: Result<T, _> = serde_json::from_str(&json);
-                if let Err(err) = body {
-                    bail!(
-                        "Url {}. Failed to deserialize body {:?}: {}",
-                        request_url,
-                        err,
-                        json
-                    );
-                }
-
-                Ok(body.unwrap())
-            }
-            StatusCode::CREATED => {
-                let json: Result<String, _> = response.text().await;
-                let Ok(json) = json else {
-                    bail!("Failed to read response body");
-                };
-                let body: Result<T, _> = serde_json::from_str(&json);
-                if let Err(err) = body {
-                    bail!("Failed to deserialize body {:?}: {}", err, json);
-                }
-
-                Ok(body.unwrap())
-            }
-            StatusCode::INTERNAL_SERVER_ERROR => {
-                bail!("Internal Server Error {}", request_url,);
-            }
-            StatusCode::SERVICE_UNAVAILABLE => {
-                bail!("Service Unavailable {}", request_url,);
-            }
-            StatusCode::UNAUTHORIZED => {
-                bail!("Unauthorized {}", request_url);
-            }
-            StatusCode::BAD_REQUEST => {
-                let error = response.text().await?;
-                bail!(format!(
-                    "Received bad request status. Url: {}. Request: {:?}. Response: {:?}",
-                    request_url, request_json, error
-                ));
-            }
-            s => {
-                let error = response.text().await?;
-
-                bail!(format!("Received response code: {s:?} error: {error:?}"));
-            }
-        }
-    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct GateFiBuyAssetParams {
     pub amount: String,
     pub crypto: String,
@@ -282,4 +327,33 @@ pub struct GateFiBuyAssetParams {
     pub redirect_url: String,
     pub region: String,
     pub wallet_address: String,
+    /// Destination tag or memo, for currencies whose `MemoSupport` allows it.
+    #[serde(default)]
+    pub wallet_memo: Option<String>,
+    /// The wallet is an exchange deposit address, which needs a memo where the chain's
+    /// `memo_required` is set.
+    #[serde(default)]
+    pub exchange_wallet: bool,
+    /// Overrides the partner fees configured on the client.
+    #[serde(default)]
+    pub partner_fee: Option<PartnerFee>,
+}
+
+impl GateFiBuyAssetParams {
+    /// Leaves out the partner fee, as its parameter names come from `PartnerFeeConfig`.
+    pub fn into_request(self, partner_account_id: String) -> GateFiBuyAssetRequest {
+        GateFiBuyAssetRequest {
+            amount: self.amount,
+            crypto: self.crypto,
+            fiat: self.fiat,
+            order_custom_id: self.order_custom_id,
+            partner_account_id,
+            payment_method: self.payment_method,
+            redirect_url: self.redirect_url,
+            region: self.region,
+            wallet_address: self.wallet_address,
+            wallet_memo: self.wallet_memo,
+            partner_fee_params: Default::default(),
+        }
+    }
 }
@@ -0,0 +1,345 @@
+use crate::rest::endpoints::GateFiEndpoint;
+use crate::rest::errors::Error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub path: String,
+    pub expected: &'static str,
+    pub actual: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaDriftReport {
+    pub endpoint: GateFiEndpoint,
+    /// Fields sent by the api which the model does not describe.
+    pub unknown_fields: Vec<String>,
+    /// Model fields the api did not send, filled with defaults.
+    pub missing_fields: Vec<String>,
+    pub type_mismatches: Vec<TypeMismatch>,
+    /// Set when the response could not be parsed into the model at all.
+    pub parse_error: Option<String>,
+}
+
+impl SchemaDriftReport {
+    pub fn new(endpoint: GateFiEndpoint) -> Self {
+        Self {
+            endpoint,
+            unknown_fields: Vec::new(),
+            missing_fields: Vec::new(),
+            type_mismatches: Vec::new(),
+            parse_error: None,
+        }
+    }
+
+    pub fn has_drift(&self) -> bool {
+        !self.unknown_fields.is_empty()
+            || !self.missing_fields.is_empty()
+            || !self.type_mismatches.is_empty()
+            || self.parse_error.is_some()
+    }
+}
+
+pub trait SchemaDriftObserver: Send + Sync {
+    fn on_drift(&self, report: &SchemaDriftReport);
+}
+
+impl<F: Fn(&SchemaDriftReport) + Send + Sync> SchemaDriftObserver for F {
+    fn on_drift(&self, report: &SchemaDriftReport) {
+        self(report)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SchemaDriftKind {
+    UnknownField,
+    MissingField,
+    TypeMismatch,
+    ParseError,
+}
+
+/// Observer counting drift occurrences per endpoint and field, e.g. to export as metrics.
+#[derive(Debug, Default)]
+pub struct SchemaDriftStats {
+    counters: Mutex<HashMap<(GateFiEndpoint, SchemaDriftKind, String), u64>>,
+}
+
+impl SchemaDriftStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> Vec<(GateFiEndpoint, SchemaDriftKind, String, u64)> {
+        let mut items: Vec<_> = self
+            .counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((endpoint, kind, path), count)| (*endpoint, *kind, path.clone(), *count))
+            .collect();
+        items.sort_by(|a, b| a.2.cmp(&b.2));
+
+        items
+    }
+}
+
+impl SchemaDriftObserver for SchemaDriftStats {
+    fn on_drift(&self, report: &SchemaDriftReport) {
+        let mut counters = self.counters.lock().unwrap();
+        let mut add = |kind: SchemaDriftKind, path: &str| {
+            *counters
+                .entry((report.endpoint, kind, path.to_string()))
+                .or_default() += 1;
+        };
+
+        for path in &report.unknown_fields {
+            add(SchemaDriftKind::UnknownField, path);
+        }
+        for path in &report.missing_fields {
+            add(SchemaDriftKind::MissingField, path);
+        }
+        for mismatch in &report.type_mismatches {
+            add(SchemaDriftKind::TypeMismatch, &mismatch.path);
+        }
+        if report.parse_error.is_some() {
+            add(SchemaDriftKind::ParseError, "");
+        }
+    }
+}
+
+/// Compares a raw response with the model parsed from it.
+///
+/// Relies on models dropping their `extra` fields on serialize.
+pub fn detect_drift<T: Serialize>(
+    endpoint: GateFiEndpoint,
+    raw: &Value,
+    model: &T,
+) -> Result<SchemaDriftReport, Error> {
+    let known = serde_json::to_value(model)?;
+
+    Ok(diff(raw, &known).into_report(endpoint))
+}
+
+/// Like `detect_drift`, for a response the model failed to parse.
+///
+/// Values whose type differs from the model's default are replaced with that default, so the
+/// rest of the response can usually still be parsed and compared.
+pub fn detect_parse_drift<T: DeserializeOwned + Serialize + Default>(
+    endpoint: GateFiEndpoint,
+    raw: &Value,
+    parse_error: String,
+) -> Result<SchemaDriftReport, Error> {
+    let expected = serde_json::to_value(T::default())?;
+    // maps and arrays are empty in the default, so only the type mismatches are meaningful here
+    let shape = diff(raw, &expected);
+
+    let mut repaired = raw.clone();
+    for mismatch in &shape.type_mismatches {
+        let pointer = json_pointer(&mismatch.path);
+        if let (Some(value), Some(default)) =
+            (repaired.pointer_mut(&pointer), expected.pointer(&pointer))
+        {
+            *value = default.clone();
+        }
+    }
+
+    let mut report = match serde_json::from_value::<T>(repaired) {
+        Ok(model) => detect_drift(endpoint, raw, &model)?,
+        Err(_) => Differences {
+            type_mismatches: shape.type_mismatches,
+            ..Differences::default()
+        }
+        .into_report(endpoint),
+    };
+    report.parse_error = Some(parse_error);
+
+    Ok(report)
+}
+
+/// Paths of fields present in `raw` but not described by `model`.
+pub fn unknown_fields<T: Serialize>(raw: &Value, model: &T) -> Result<Vec<String>, Error> {
+    let known = serde_json::to_value(model)?;
+
+    Ok(diff(raw, &known).unknown_fields)
+}
+
+#[derive(Default)]
+struct Differences {
+    unknown_fields: Vec<String>,
+    missing_fields: Vec<String>,
+    type_mismatches: Vec<TypeMismatch>,
+}
+
+impl Differences {
+    fn into_report(self, endpoint: GateFiEndpoint) -> SchemaDriftReport {
+        SchemaDriftReport {
+            unknown_fields: self.unknown_fields,
+            missing_fields: self.missing_fields,
+            type_mismatches: self.type_mismatches,
+            ..SchemaDriftReport::new(endpoint)
+        }
+    }
+}
+
+fn diff(raw: &Value, known: &Value) -> Differences {
+    let mut differences = Differences::default();
+    compare("", raw, known, &mut differences);
+
+    differences
+}
+
+fn compare(path: &str, raw: &Value, known: &Value, report: &mut Differences) {
+    match (raw, known) {
+        (Value::Object(raw), Value::Object(known)) => {
+            for (key, raw_value) in raw {
+                let field_path = join_path(path, key);
+                match known.get(key) {
+                    Some(known_value) => compare(&field_path, raw_value, known_value, report),
+                    None => report.unknown_fields.push(field_path),
+                }
+            }
+            for key in known.keys() {
+                if !raw.contains_key(key) {
+                    report.missing_fields.push(join_path(path, key));
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(known)) => {
+            for (index, (raw_value, known_value)) in raw.iter().zip(known).enumerate() {
+                compare(
+                    &join_path(path, &index.to_string()),
+                    raw_value,
+                    known_value,
+                    report,
+                );
+            }
+        }
+        (Value::Null, _) | (_, Value::Null) => {}
+        (raw, known) => {
+            if type_name(raw) != type_name(known) {
+                report.type_mismatches.push(TypeMismatch {
+                    path: path.to_string(),
+                    expected: type_name(known),
+                    actual: type_name(raw),
+                });
+            }
+        }
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn json_pointer(path: &str) -> String {
+    path.split('.')
+        .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+pub(crate) fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::models::{GateFiPlatformConfigResponse, GetQuoteResponse};
+
+    #[test]
+    fn detect_drift_fields() {
+        let json = r#"{"version":"1","features":{"buy":{"enabled":true},"sell":{"enabled":true}},"countries":[{"id":"DE","flag":"de"}]}"#;
+        let raw: Value = serde_json::from_str(json).unwrap();
+        let model: GateFiPlatformConfigResponse = serde_json::from_str(json).unwrap();
+
+        assert!(model.features.is_enabled("sell"));
+        assert!(!model.features.is_enabled("quotes"));
+        let report = detect_drift(GateFiEndpoint::PlatformConfig, &raw, &model).unwrap();
+        assert_eq!(
+            vec!["countries.0.flag".to_string(), "features.sell".to_string()],
+            report.unknown_fields
+        );
+        assert!(report.missing_fields.contains(&"updatedAt".to_string()));
+        assert!(report.type_mismatches.is_empty());
+    }
+
+    #[test]
+    fn detect_parse_drift_paths() {
+        let json = r#"{"amountOut":5,"networkFee":"0.1","fee":"1"}"#;
+        let raw: Value = serde_json::from_str(json).unwrap();
+        let err = serde_json::from_str::<GetQuoteResponse>(json).unwrap_err();
+
+        let report =
+            detect_parse_drift::<GetQuoteResponse>(GateFiEndpoint::Quotes, &raw, err.to_string())
+                .unwrap();
+        assert_eq!(
+            vec![TypeMismatch {
+                path: "amountOut".to_string(),
+                expected: "string",
+                actual: "number",
+            }],
+            report.type_mismatches
+        );
+        assert_eq!(vec!["fee".to_string()], report.unknown_fields);
+        assert_eq!(vec!["processingFee".to_string()], report.missing_fields);
+        assert!(report.parse_error.is_some());
+        assert_eq!(
+            vec!["fee".to_string()],
+            unknown_fields(&raw, &GetQuoteResponse::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn count_drift() {
+        let stats = SchemaDriftStats::new();
+        let mut report = SchemaDriftReport::new(GateFiEndpoint::Quotes);
+        report.unknown_fields.push("fee".to_string());
+        report.type_mismatches.push(TypeMismatch {
+            path: "amountOut".to_string(),
+            expected: "string",
+            actual: "number",
+        });
+        stats.on_drift(&report);
+        stats.on_drift(&report);
+
+        let seen = Mutex::new(Vec::new());
+        let observer = |report: &SchemaDriftReport| {
+            seen.lock().unwrap().push(report.unknown_fields.clone());
+        };
+        observer.on_drift(&report);
+        assert_eq!(vec![vec!["fee".to_string()]], *seen.lock().unwrap());
+
+        assert_eq!(
+            vec![
+                (
+                    GateFiEndpoint::Quotes,
+                    SchemaDriftKind::TypeMismatch,
+                    "amountOut".to_string(),
+                    2
+                ),
+                (
+                    GateFiEndpoint::Quotes,
+                    SchemaDriftKind::UnknownField,
+                    "fee".to_string(),
+                    2
+                ),
+            ],
+            stats.snapshot()
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use crate::rest::endpoints::RawResponse;
+use crate::rest::errors::Error;
+use async_trait::async_trait;
+use error_chain::bail;
+use reqwest::header::HeaderMap;
+
+#[derive(Debug, Clone)]
+pub struct PreparedRequest {
+    pub method: reqwest::Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<String>,
+    pub follow_redirects: bool,
+}
+
+/// HTTP layer of `GateFiRestClient`; requests arrive signed and ready to send.
+#[async_trait]
+pub trait GateFiTransport: Send + Sync {
+    async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error>;
+}
+
+#[derive(Clone)]
+pub struct ReqwestTransport {
+    inner_client: reqwest::Client,
+    no_redirect_client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        Self::with_client_builder(reqwest::Client::builder).expect("must be valid client")
+    }
+
+    /// `builder` configures both clients: the one following redirects and the one which does not.
+    pub fn with_client_builder(
+        builder: impl Fn() -> reqwest::ClientBuilder,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            inner_client: builder().build()?,
+            no_redirect_client: builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()?,
+        })
+    }
+}
+
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl GateFiTransport for ReqwestTransport {
+    async fn send(&self, request: &PreparedRequest) -> Result<RawResponse, Error> {
+        let client = if request.follow_redirects {
+            &self.inner_client
+        } else {
+            &self.no_redirect_client
+        };
+        let mut builder = client
+            .request(request.method.clone(), request.url.as_str())
+            .headers(request.headers.clone());
+        if let Some(body) = &request.body {
+            builder = builder.body(body.clone());
+        }
+        let response = builder.send().await?;
+
+        let status = response.status().as_u16();
+        let url = response.url().to_string();
+        let headers = response.headers().clone();
+        let json: Result<String, _> = response.text().await;
+        let Ok(json) = json else {
+            bail!("Failed to read response body. Url {}", request.url);
+        };
+
+        Ok(RawResponse {
+            status,
+            url,
+            headers,
+            body: json,
+        })
+    }
+}
@@ -0,0 +1,270 @@
+use crate::rest::errors::Error;
+use crate::rest::models::{GateFiCallbackData, GateFiTransactionStatus, GetQuoteResponse};
+use error_chain::bail;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct QuotedOrder {
+    pub order_id: String,
+    pub crypto_currency: String,
+    pub fiat_currency: String,
+    /// `amount_out` of the quote shown to the user.
+    pub quoted_amount: Decimal,
+    pub quoted_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SlippageResult {
+    pub order_id: String,
+    pub transaction_id: String,
+    pub crypto_currency: String,
+    pub fiat_currency: String,
+    pub quoted_amount: Decimal,
+    pub settled_amount: Decimal,
+    /// Settled minus quoted amount; negative when the user received less.
+    pub slippage: Decimal,
+    pub slippage_percent: Decimal,
+    pub within_tolerance: bool,
+    pub quoted_at: u64,
+    pub settled_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SlippageSummary {
+    pub count: usize,
+    pub flagged: usize,
+    pub average_percent: Decimal,
+    /// Largest deviation in either direction.
+    pub worst_percent: Decimal,
+}
+
+/// Compares quotes shown to users with the crypto amount of their succeeded callbacks.
+#[derive(Debug, Default)]
+pub struct SlippageTracker {
+    tolerance_percent: Decimal,
+    state: Mutex<SlippageState>,
+}
+
+#[derive(Debug, Default)]
+struct SlippageState {
+    quotes: HashMap<String, QuotedOrder>,
+    results: Vec<SlippageResult>,
+}
+
+impl SlippageTracker {
+    /// Deviations above `tolerance_percent` in either direction are flagged, e.g. `1` for 1%.
+    pub fn new(tolerance_percent: Decimal) -> Self {
+        Self {
+            tolerance_percent: tolerance_percent.abs(),
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn tolerance_percent(&self) -> Decimal {
+        self.tolerance_percent
+    }
+
+    /// Stores the quote for `order_id`, replacing an earlier one.
+    pub fn record_quote(
+        &self,
+        order_id: &str,
+        crypto_currency: &str,
+        fiat_currency: &str,
+        quote: &GetQuoteResponse,
+    ) -> Result<(), Error> {
+        let quoted_amount = match Decimal::from_str(quote.amount_out.trim()) {
+            Ok(amount) if amount > Decimal::ZERO => amount,
+            Ok(amount) => bail!("Quote for {} has no amount out: {}", order_id, amount),
+            Err(err) => bail!("Invalid amountOut {}: {}", quote.amount_out, err),
+        };
+        let quoted = QuotedOrder {
+            order_id: order_id.to_string(),
+            crypto_currency: crypto_currency.to_uppercase(),
+            fiat_currency: fiat_currency.to_uppercase(),
+            quoted_amount,
+            quoted_at: unix_micros()?,
+        };
+        self.state
+            .lock()
+            .unwrap()
+            .quotes
+            .insert(order_id.to_string(), quoted);
+
+        Ok(())
+    }
+
+    pub fn quote(&self, order_id: &str) -> Option<QuotedOrder> {
+        self.state.lock().unwrap().quotes.get(order_id).cloned()
+    }
+
+    /// Matches the callback by `custom_order_id`. Only succeeded callbacks produce a result,
+    /// any terminal status drops the quote, and callbacks without a quote are ignored.
+    /// A succeeded callback which can not be compared keeps the quote and returns `Err`.
+    pub fn ingest(&self, callback: &GateFiCallbackData) -> Result<Option<SlippageResult>, Error> {
+        let status = callback.parsed_status();
+        if !status.is_terminal() {
+            return Ok(None);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if status != GateFiTransactionStatus::Succeeded {
+            state.quotes.remove(&callback.custom_order_id);
+            return Ok(None);
+        }
+        let Some(quoted) = state.quotes.get(&callback.custom_order_id).cloned() else {
+            return Ok(None);
+        };
+        if !quoted
+            .crypto_currency
+            .eq_ignore_ascii_case(&callback.crypto_currency)
+        {
+            bail!(
+                "Order {} was quoted in {} but settled in {}",
+                quoted.order_id,
+                quoted.crypto_currency,
+                callback.crypto_currency
+            );
+        }
+        let settled_amount = match callback.parsed_crypto_amount() {
+            Ok(amount) => amount,
+            Err(err) => bail!("Transaction {}: {}", callback.transaction_id, err),
+        };
+
+        let slippage = settled_amount - quoted.quoted_amount;
+        let slippage_percent = (slippage / quoted.quoted_amount * Decimal::ONE_HUNDRED).round_dp(4);
+        let result = SlippageResult {
+            order_id: quoted.order_id,
+            transaction_id: callback.transaction_id.clone(),
+            crypto_currency: quoted.crypto_currency,
+            fiat_currency: quoted.fiat_currency,
+            quoted_amount: quoted.quoted_amount,
+            settled_amount,
+            slippage,
+            slippage_percent,
+            within_tolerance: slippage_percent.abs() <= self.tolerance_percent,
+            quoted_at: quoted.quoted_at,
+            settled_at: unix_micros()?,
+        };
+        state.quotes.remove(&callback.custom_order_id);
+        state.results.push(result.clone());
+
+        Ok(Some(result))
+    }
+
+    pub fn results(&self) -> Vec<SlippageResult> {
+        self.state.lock().unwrap().results.clone()
+    }
+
+    pub fn flagged(&self) -> Vec<SlippageResult> {
+        self.state
+            .lock()
+            .unwrap()
+            .results
+            .iter()
+            .filter(|result| !result.within_tolerance)
+            .cloned()
+            .collect()
+    }
+
+    pub fn summary(&self) -> SlippageSummary {
+        let state = self.state.lock().unwrap();
+        let count = state.results.len();
+        let total: Decimal = state
+            .results
+            .iter()
+            .map(|result| result.slippage_percent)
+            .sum();
+
+        SlippageSummary {
+            count,
+            flagged: state
+                .results
+                .iter()
+                .filter(|result| !result.within_tolerance)
+                .count(),
+            average_percent: match count {
+                0 => Decimal::ZERO,
+                count => (total / Decimal::from(count)).round_dp(4),
+            },
+            worst_percent: state
+                .results
+                .iter()
+                .map(|result| result.slippage_percent)
+                .max_by_key(|percent| percent.abs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn unix_micros() -> Result<u64, Error> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhook::simulator::GateFiWebhookSimulator;
+
+    fn quote(amount_out: &str) -> GetQuoteResponse {
+        serde_json::from_str(&format!(r#"{{"amountOut":"{amount_out}"}}"#)).unwrap()
+    }
+
+    fn callback(order_id: &str, crypto_amount: &str) -> GateFiCallbackData {
+        let mut callback =
+            GateFiWebhookSimulator::sample_callback(GateFiTransactionStatus::Succeeded);
+        callback.custom_order_id = order_id.to_string();
+        callback.crypto_amount = crypto_amount.to_string();
+
+        callback
+    }
+
+    #[test]
+    fn detect_slippage() {
+        let tracker = SlippageTracker::new(Decimal::ONE);
+        tracker
+            .record_quote("a", "btc", "usd", &quote("0.002"))
+            .unwrap();
+        tracker
+            .record_quote("b", "BTC", "USD", &quote("0.002"))
+            .unwrap();
+
+        let small = tracker.ingest(&callback("a", "0.00199")).unwrap().unwrap();
+        assert_eq!(Decimal::new(-1, 5), small.slippage);
+        assert_eq!(Decimal::new(-5, 1), small.slippage_percent);
+        assert!(small.within_tolerance);
+
+        let large = tracker.ingest(&callback("b", "0.0019")).unwrap().unwrap();
+        assert_eq!(Decimal::new(-5, 0), large.slippage_percent);
+        assert!(!large.within_tolerance);
+
+        assert!(tracker.ingest(&callback("a", "0.002")).unwrap().is_none());
+        assert_eq!(vec![large], tracker.flagged());
+
+        let summary = tracker.summary();
+        assert_eq!(2, summary.count);
+        assert_eq!(Decimal::new(-275, 2), summary.average_percent);
+        assert_eq!(Decimal::new(-5, 0), summary.worst_percent);
+    }
+
+    #[test]
+    fn keep_quote_on_error() {
+        let tracker = SlippageTracker::new(Decimal::ONE);
+        tracker
+            .record_quote("a", "BTC", "USD", &quote("0.002"))
+            .unwrap();
+
+        assert!(tracker.ingest(&callback("a", "not a number")).is_err());
+        let mut eth = callback("a", "0.002");
+        eth.crypto_currency = "ETH".to_string();
+        assert!(tracker.ingest(&eth).is_err());
+        assert!(tracker.quote("a").is_some());
+
+        assert!(tracker.ingest(&callback("a", "0.002")).unwrap().is_some());
+        assert!(tracker.quote("a").is_none());
+    }
+}
@@ -0,0 +1,80 @@
+use crate::webhook::{
+    GateFiCallbackHandler, GateFiPartnerCallbackHandler, GateFiPoolWebhookProcessor,
+    GateFiWebhookProcessor, WebhookOutcome, SIGNATURE_HEADER,
+};
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use std::sync::Arc;
+
+/// The returned router is a tower `Service` and can be nested or served directly.
+pub fn router<H: GateFiCallbackHandler>(
+    path: &str,
+    processor: Arc<GateFiWebhookProcessor<H>>,
+) -> Router {
+    Router::new()
+        .route(path, post(handle_webhook::<H>))
+        .with_state(processor)
+}
+
+/// Serves `{path}` for any partner and `{path}/{partner}` for a known partner key.
+pub fn pool_router<H: GateFiPartnerCallbackHandler>(
+    path: &str,
+    processor: Arc<GateFiPoolWebhookProcessor<H>>,
+) -> Router {
+    Router::new()
+        .route(path, post(handle_pool_webhook::<H>))
+        .route(
+            &format!("{}/{{partner}}", path.trim_end_matches('/')),
+            post(handle_partner_webhook::<H>),
+        )
+        .with_state(processor)
+}
+
+pub async fn handle_webhook<H: GateFiCallbackHandler>(
+    State(processor): State<Arc<GateFiWebhookProcessor<H>>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, String) {
+    let outcome = processor.process(&body, signature(&headers)).await;
+
+    response(outcome)
+}
+
+pub async fn handle_pool_webhook<H: GateFiPartnerCallbackHandler>(
+    State(processor): State<Arc<GateFiPoolWebhookProcessor<H>>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, String) {
+    let outcome = processor.process(None, &body, signature(&headers)).await;
+
+    response(outcome)
+}
+
+pub async fn handle_partner_webhook<H: GateFiPartnerCallbackHandler>(
+    State(processor): State<Arc<GateFiPoolWebhookProcessor<H>>>,
+    Path(partner): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, String) {
+    let outcome = processor
+        .process(Some(&partner), &body, signature(&headers))
+        .await;
+
+    response(outcome)
+}
+
+fn signature(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+}
+
+fn response(outcome: WebhookOutcome) -> (StatusCode, String) {
+    let status =
+        StatusCode::from_u16(outcome.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+    (status, outcome.response_body().to_string())
+}
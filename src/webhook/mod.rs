@@ -0,0 +1,246 @@
+#[cfg(feature = "axum")]
+pub mod axum;
+pub mod simulator;
+
+use crate::client_pool::GateFiClientPool;
+use crate::rest::config::GateFiConfig;
+use crate::rest::errors::{Error, ErrorKind};
+use crate::rest::models::GateFiCallbackData;
+use crate::rest::request_signer::GateFiSigner;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub const SIGNATURE_HEADER: &str = "signature";
+
+#[derive(Debug, Clone)]
+pub struct GateFiWebhookVerifier {
+    secret_key: String,
+}
+
+impl GateFiWebhookVerifier {
+    pub fn new(secret_key: String) -> Self {
+        Self { secret_key }
+    }
+
+    pub fn from_config(config: &GateFiConfig) -> Self {
+        Self::new(config.webhook_secret().to_string())
+    }
+
+    pub fn verify(&self, body: &str, signature: &str) -> Result<GateFiCallbackData, Error> {
+        if !GateFiSigner::verify_sign(&self.secret_key, body, signature) {
+            return Err(ErrorKind::InvalidSignature.into());
+        }
+
+        Ok(serde_json::from_str(body)?)
+    }
+}
+
+#[async_trait]
+pub trait GateFiCallbackHandler: Send + Sync + 'static {
+    async fn handle(&self, callback: GateFiCallbackData) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait GateFiPartnerCallbackHandler: Send + Sync + 'static {
+    async fn handle(&self, partner: &str, callback: GateFiCallbackData) -> Result<(), Error>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookOutcome {
+    Accepted,
+    MissingSignature,
+    InvalidSignature,
+    UnknownPartner(String),
+    InvalidPayload(String),
+    HandlerFailed(String),
+}
+
+impl WebhookOutcome {
+    /// Only handler failures map to 5xx, so GateFi does not retry requests that can never succeed.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            WebhookOutcome::Accepted => 200,
+            WebhookOutcome::MissingSignature | WebhookOutcome::InvalidSignature => 401,
+            WebhookOutcome::InvalidPayload(_) => 400,
+            WebhookOutcome::UnknownPartner(_) => 404,
+            WebhookOutcome::HandlerFailed(_) => 500,
+        }
+    }
+
+    /// Fixed response body; details stay in the outcome and are not sent to the caller.
+    pub fn response_body(&self) -> &'static str {
+        match self {
+            WebhookOutcome::Accepted => "OK",
+            WebhookOutcome::MissingSignature => "Missing signature",
+            WebhookOutcome::InvalidSignature => "Invalid signature",
+            WebhookOutcome::UnknownPartner(_) => "Unknown partner",
+            WebhookOutcome::InvalidPayload(_) => "Invalid payload",
+            WebhookOutcome::HandlerFailed(_) => "Internal error",
+        }
+    }
+}
+
+pub struct GateFiWebhookProcessor<H: GateFiCallbackHandler> {
+    verifier: GateFiWebhookVerifier,
+    handler: Arc<H>,
+}
+
+impl<H: GateFiCallbackHandler> GateFiWebhookProcessor<H> {
+    pub fn new(secret_key: String, handler: Arc<H>) -> Self {
+        Self {
+            verifier: GateFiWebhookVerifier::new(secret_key),
+            handler,
+        }
+    }
+
+    pub async fn process(&self, body: &[u8], signature: Option<&str>) -> WebhookOutcome {
+        let Some(signature) = signature else {
+            return WebhookOutcome::MissingSignature;
+        };
+        let Ok(body) = std::str::from_utf8(body) else {
+            return WebhookOutcome::InvalidPayload("Body is not valid utf-8".to_string());
+        };
+
+        let callback = match self.verifier.verify(body, signature) {
+            Ok(callback) => callback,
+            Err(Error(ErrorKind::InvalidSignature, _)) => return WebhookOutcome::InvalidSignature,
+            Err(err) => return WebhookOutcome::InvalidPayload(err.to_string()),
+        };
+
+        match self.handler.handle(callback).await {
+            Ok(()) => WebhookOutcome::Accepted,
+            Err(err) => WebhookOutcome::HandlerFailed(err.to_string()),
+        }
+    }
+}
+
+/// Routes callbacks of several partners to the right secret, by key or by trying each secret.
+pub struct GateFiPoolWebhookProcessor<H: GateFiPartnerCallbackHandler> {
+    pool: Arc<GateFiClientPool>,
+    handler: Arc<H>,
+}
+
+impl<H: GateFiPartnerCallbackHandler> GateFiPoolWebhookProcessor<H> {
+    pub fn new(pool: Arc<GateFiClientPool>, handler: Arc<H>) -> Self {
+        Self { pool, handler }
+    }
+
+    pub async fn process(
+        &self,
+        partner: Option<&str>,
+        body: &[u8],
+        signature: Option<&str>,
+    ) -> WebhookOutcome {
+        let Some(signature) = signature else {
+            return WebhookOutcome::MissingSignature;
+        };
+        let Ok(body) = std::str::from_utf8(body) else {
+            return WebhookOutcome::InvalidPayload("Body is not valid utf-8".to_string());
+        };
+
+        let verified = match partner {
+            Some(partner) if !self.pool.contains(partner) => {
+                return WebhookOutcome::UnknownPartner(partner.to_string())
+            }
+            Some(partner) => self
+                .pool
+                .verify_webhook(partner, body, signature)
+                .map(|callback| (partner.to_string(), callback)),
+            None => self.pool.identify_webhook(body, signature),
+        };
+        let (partner, callback) = match verified {
+            Ok(verified) => verified,
+            Err(Error(ErrorKind::InvalidSignature, _)) => return WebhookOutcome::InvalidSignature,
+            Err(err) => return WebhookOutcome::InvalidPayload(err.to_string()),
+        };
+
+        match self.handler.handle(&partner, callback).await {
+            Ok(()) => WebhookOutcome::Accepted,
+            Err(err) => WebhookOutcome::HandlerFailed(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rest::models::GateFiTransactionStatus;
+    use crate::webhook::simulator::GateFiWebhookSimulator;
+    use error_chain::bail;
+
+    struct FailingHandler;
+
+    #[async_trait]
+    impl GateFiCallbackHandler for FailingHandler {
+        async fn handle(&self, callback: GateFiCallbackData) -> Result<(), Error> {
+            if callback.status == "failed" {
+                bail!("database is down");
+            }
+
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn outcome_status_codes() {
+        let simulator = GateFiWebhookSimulator::new("secret".to_string());
+        let processor = GateFiWebhookProcessor::new("secret".to_string(), Arc::new(FailingHandler));
+        let body = serde_json::to_string(&GateFiWebhookSimulator::sample_callback(
+            GateFiTransactionStatus::Created,
+        ))
+        .unwrap();
+        let failed = serde_json::to_string(&GateFiWebhookSimulator::sample_callback(
+            GateFiTransactionStatus::Failed,
+        ))
+        .unwrap();
+
+        let cases = [
+            (
+                processor
+                    .process(body.as_bytes(), Some(&simulator.sign(&body)))
+                    .await,
+                200,
+            ),
+            (processor.process(body.as_bytes(), None).await, 401),
+            (processor.process(body.as_bytes(), Some("00")).await, 401),
+            (
+                processor.process(b"{}", Some(&simulator.sign("{}"))).await,
+                400,
+            ),
+            (
+                processor
+                    .process(failed.as_bytes(), Some(&simulator.sign(&failed)))
+                    .await,
+                500,
+            ),
+        ];
+        for (outcome, status) in cases {
+            assert_eq!(status, outcome.status_code(), "{outcome:?}");
+        }
+
+        let outcome = processor
+            .process(failed.as_bytes(), Some(&simulator.sign(&failed)))
+            .await;
+        assert_eq!("Internal error", outcome.response_body());
+        assert_eq!(
+            404,
+            WebhookOutcome::UnknownPartner("x".to_string()).status_code()
+        );
+    }
+
+    #[test]
+    fn verify_keeps_memo() {
+        let simulator = GateFiWebhookSimulator::new("secret".to_string());
+        let mut callback =
+            GateFiWebhookSimulator::sample_callback(GateFiTransactionStatus::Succeeded);
+        callback.crypto_currency = "XRP".to_string();
+        callback.destination_wallet_memo = Some("12345".to_string());
+        let body = serde_json::to_string(&callback).unwrap();
+        assert!(body.contains(r#""destinationWalletMemo":"12345""#));
+
+        let verified = GateFiWebhookVerifier::new("secret".to_string())
+            .verify(&body, &simulator.sign(&body))
+            .unwrap();
+        assert_eq!(Some("12345".to_string()), verified.destination_wallet_memo);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::rest::errors::Error;
+use crate::rest::models::{GateFiCallbackData, GateFiTransactionStatus};
+use crate::rest::request_signer::GateFiSigner;
+use crate::webhook::SIGNATURE_HEADER;
+use error_chain::bail;
+use serde_json::value::RawValue;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct WebhookDelivery {
+    pub status: u16,
+    pub response_body: String,
+    pub request_body: String,
+    pub signature: String,
+}
+
+/// Signs callbacks the same way `GateFiWebhookVerifier` expects and posts them to a local handler.
+#[derive(Clone)]
+pub struct GateFiWebhookSimulator {
+    secret_key: String,
+    inner_client: reqwest::Client,
+}
+
+impl GateFiWebhookSimulator {
+    pub fn new(secret_key: String) -> Self {
+        Self {
+            secret_key,
+            inner_client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn sample_callback(status: GateFiTransactionStatus) -> GateFiCallbackData {
+        GateFiCallbackData {
+            crypto_amount: "0.00163967".to_string(),
+            crypto_currency: "BTC".to_string(),
+            custom_order_id: "simulated-order".to_string(),
+            destination_wallet: "mjEcj2LA3vj1nDi8ZD3QMCs9kNqVk7Dpee".to_string(),
+            destination_wallet_memo: None,
+            fiat_currency: "USD".to_string(),
+            fiat_amount: "50".to_string(),
+            tap_on_fee_amount: None,
+            tap_on_fee_currency: None,
+            transaction_hashes: match status {
+                GateFiTransactionStatus::Succeeded => Some(vec![
+                    "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b".to_string(),
+                ]),
+                _ => None,
+            },
+            transaction_id: "cbd38c1b-721a-4fa5-948d-8a628073084b".to_string(),
+            status: status.to_string(),
+            fail_reason: match status {
+                GateFiTransactionStatus::Failed => Some("simulated failure".to_string()),
+                _ => None,
+            },
+            payment_method: Some("BANKCARD".to_string()),
+            extra: Default::default(),
+        }
+    }
+
+    pub fn sign(&self, body: &str) -> String {
+        GateFiSigner::generate_sign(&self.secret_key, body)
+    }
+
+    pub async fn deliver(
+        &self,
+        url: &str,
+        callback: &GateFiCallbackData,
+    ) -> Result<WebhookDelivery, Error> {
+        let body = serde_json::to_string(callback)?;
+
+        self.deliver_raw(url, body).await
+    }
+
+    pub async fn deliver_raw(&self, url: &str, body: String) -> Result<WebhookDelivery, Error> {
+        let signature = self.sign(&body);
+        let response = self
+            .inner_client
+            .post(url)
+            .header(SIGNATURE_HEADER, &signature)
+            .header("content-type", "application/json")
+            .body(body.clone())
+            .send()
+            .await?;
+
+        Ok(WebhookDelivery {
+            status: response.status().as_u16(),
+            response_body: response.text().await?,
+            request_body: body,
+            signature,
+        })
+    }
+
+    /// Replays a file with a single captured payload or an array of payloads, in order.
+    /// Payloads are sent byte for byte as captured and signed over those exact bytes.
+    pub async fn replay_file(
+        &self,
+        url: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<WebhookDelivery>, Error> {
+        let content = tokio::fs::read_to_string(path).await?;
+
+        let mut deliveries = Vec::new();
+        for payload in captured_payloads(&content)? {
+            deliveries.push(self.deliver_raw(url, payload).await?);
+        }
+
+        Ok(deliveries)
+    }
+}
+
+/// A single object is returned unchanged; array elements are cut out of the original text.
+fn captured_payloads(content: &str) -> Result<Vec<String>, Error> {
+    match content.trim_start().chars().next() {
+        Some('{') => {
+            serde_json::from_str::<&RawValue>(content)?;
+            Ok(vec![content.to_string()])
+        }
+        Some('[') => {
+            let payloads: Vec<&RawValue> = serde_json::from_str(content)?;
+            if !payloads
+                .iter()
+                .all(|payload| payload.get().starts_with('{'))
+            {
+                bail!("Captured webhooks must be a json object or an array of objects");
+            }
+
+            Ok(payloads
+                .iter()
+                .map(|payload| payload.get().to_string())
+                .collect())
+        }
+        _ => bail!("Captured webhooks must be a json object or an array of objects"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhook::GateFiWebhookVerifier;
+
+    #[test]
+    fn signed_sample_passes_verification() {
+        let secret = "JHDOFKyrhlonPfoXrXiMeFqKRhWYdXTv".to_string();
+        let simulator = GateFiWebhookSimulator::new(secret.clone());
+        let callback = GateFiWebhookSimulator::sample_callback(GateFiTransactionStatus::Failed);
+        let body = serde_json::to_string(&callback).unwrap();
+
+        let verified = GateFiWebhookVerifier::new(secret)
+            .verify(&body, &simulator.sign(&body))
+            .unwrap();
+
+        assert_eq!("failed", verified.status);
+        assert_eq!(Some("simulated failure".to_string()), verified.fail_reason);
+    }
+
+    #[test]
+    fn captured_payloads_keep_raw_bytes() {
+        let single = "{\"b\": 1.50, \"a\": \"x\"}\n";
+        assert_eq!(vec![single.to_string()], captured_payloads(single).unwrap());
+
+        let array = "[ {\"b\": 1.50,\n \"a\": 1e2}, {\"z\":0} ]";
+        assert_eq!(
+            vec![
+                "{\"b\": 1.50,\n \"a\": 1e2}".to_string(),
+                "{\"z\":0}".to_string()
+            ],
+            captured_payloads(array).unwrap()
+        );
+        assert!(captured_payloads("[1]").is_err());
+        assert!(captured_payloads("\"text\"").is_err());
+    }
+}